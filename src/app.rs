@@ -1,183 +1,1125 @@
-use std::time::{Duration, Instant};
-use crate::snippets::{Language, MENU_OPTIONS, random_snippet};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use rhonetyping::clock::{Clock, SystemClock};
+use rhonetyping::engine::{ErrorPolicy, Key, Session, Settings, WhitespacePolicy, pace_chars};
+
+use crate::config::{Config, KeyBindings};
+use crate::drill::{self, DRILL_WORDS};
+use crate::ghost::{Ghost, GhostMode};
+use crate::keystats::{KeySort, KeyStats};
+use crate::history::{Comparison, History, HistorySort, SessionRecord, now_millis, now_timestamp};
+use crate::race::{Race, Update};
+use crate::recording::{Playback, Recording};
+use crate::snippets::{LANGUAGES, Language, Library, MenuOption, Snippet};
+use crate::source::SourceSnippets;
+use crate::theme::Theme;
+use crate::words::{WordList, WordOptions, Words};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Screen {
     Menu,
     Typing,
     Results,
+    History,
+    Stats,
+    Themes,
+    Settings,
+    Replay,
+    /// Waiting for a race over the network to start
+    Lobby,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum CharState {
-    Untyped,
-    Correct,
-    Wrong,
+/// What ends a session. Written as `snippet`, `time:<secs>` or
+/// `words:<count>` in the config file and on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Mode {
+    /// Type one snippet to the end
+    Snippet,
+    /// Type a continuous stream of text until the timer (seconds) runs out
+    Timed(u64),
+    /// Type this many words generated from the word list
+    Words(usize),
+}
+
+/// Modes offered in the menu, in cycling order
+pub const MODES: &[Mode] = &[
+    Mode::Snippet,
+    Mode::Timed(15),
+    Mode::Timed(30),
+    Mode::Timed(60),
+    Mode::Timed(120),
+    Mode::Words(10),
+    Mode::Words(25),
+    Mode::Words(50),
+    Mode::Words(100),
+];
+
+/// Default rows of context around the cursor in the snippet pane
+pub const SCROLL_CONTEXT: usize = 2;
+/// The most context rows the Settings screen offers
+const MAX_SCROLL_CONTEXT: usize = 5;
+/// Pace caret speeds offered on the Settings screen go up in these steps
+const PACE_STEP: u32 = 10;
+const MAX_PACE_WPM: u32 = 200;
+
+/// Timed mode keeps at least this many untyped characters queued up
+const STREAM_LOOKAHEAD: usize = 200;
+
+impl Mode {
+    pub fn label(&self) -> String {
+        match self {
+            Mode::Snippet => "snippet".to_string(),
+            Mode::Timed(secs) => format!("time {}s", secs),
+            Mode::Words(count) => format!("{} words", count),
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Mode> {
+        match text.split_once(':') {
+            None if text == "snippet" => Some(Mode::Snippet),
+            Some(("time", secs)) => secs.parse().ok().filter(|s| *s > 0).map(Mode::Timed),
+            Some(("words", count)) => count.parse().ok().filter(|c| *c > 0).map(Mode::Words),
+            _ => None,
+        }
+    }
+
+    pub fn time_limit(&self) -> Option<Duration> {
+        match self {
+            Mode::Timed(secs) => Some(Duration::from_secs(*secs)),
+            Mode::Snippet | Mode::Words(_) => None,
+        }
+    }
+}
+
+impl TryFrom<String> for Mode {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Mode, String> {
+        Mode::parse(&text).ok_or_else(|| format!("invalid mode {:?} (snippet, time:<secs> or words:<count>)", text))
+    }
+}
+
+impl From<Mode> for String {
+    fn from(mode: Mode) -> String {
+        match mode {
+            Mode::Snippet => "snippet".to_string(),
+            Mode::Timed(secs) => format!("time:{}", secs),
+            Mode::Words(count) => format!("words:{}", count),
+        }
+    }
+}
+
+/// A row at the top of the Settings screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Preference {
+    Language,
+    Mode,
+    Theme,
+    Cursor,
+    Whitespace,
+    StopOnError,
+    Backspace,
+    ScrollContext,
+    Ghost,
+    PaceCaret,
+}
+
+/// Preference rows in the order the Settings screen lists them
+pub const PREFERENCES: &[Preference] = &[
+    Preference::Language,
+    Preference::Mode,
+    Preference::Theme,
+    Preference::Cursor,
+    Preference::Whitespace,
+    Preference::StopOnError,
+    Preference::Backspace,
+    Preference::ScrollContext,
+    Preference::Ghost,
+    Preference::PaceCaret,
+];
+
+impl Preference {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Preference::Language => "language",
+            Preference::Mode => "mode",
+            Preference::Theme => "theme",
+            Preference::Cursor => "cursor",
+            Preference::Whitespace => "whitespace",
+            Preference::StopOnError => "stop on error",
+            Preference::Backspace => "backspace",
+            Preference::ScrollContext => "scroll context",
+            Preference::Ghost => "ghost",
+            Preference::PaceCaret => "pace caret",
+        }
+    }
 }
 
+/// Rows of the Settings screen: the preferences, then one row per key binding
+pub const SETTINGS_ROWS: usize = PREFERENCES.len() + KeyBindings::COUNT;
+
 pub struct App {
     pub screen: Screen,
     pub selected_menu: usize,
+    pub menu: Vec<MenuOption>,
+    /// The menu entry the current session was started from
+    pub option: MenuOption,
     pub language: Language,
+    pub mode: Mode,
+    pub whitespace: WhitespacePolicy,
+    pub error_policy: ErrorPolicy,
+    pub library: Library,
+    /// Snippets extracted from `--source` paths; preferred over the built-ins
+    pub sources: SourceSnippets,
+    pub words: Words,
+    pub word_list: WordList,
+    pub word_options: WordOptions,
 
     // Typing state
-    pub snippet: String,
-    pub chars: Vec<char>,
-    pub char_states: Vec<CharState>,
-    pub cursor: usize,
-    pub errors: usize,
+    pub snippet: Snippet,
+    pub session: Session,
+    /// The cursor racing this session, if the config asks for one
+    pub ghost: Option<Ghost>,
+    /// Stamps every keypress and tick; swapped for a manual clock in tests
+    pub clock: Box<dyn Clock>,
+
+    // History
+    pub history: History,
+    pub history_sort: HistorySort,
+    /// Language shown on the History screen; `None` shows all
+    pub history_filter: Option<Language>,
+    pub history_scroll: usize,
+    /// Why the last session couldn't be written to disk, if it couldn't
+    pub save_error: Option<String>,
+    /// The last finished session measured against earlier ones
+    pub comparison: Comparison,
+    /// The last finished session's keystrokes, for replaying from the results
+    pub recording: Option<Recording>,
+    /// Why the selected history entry couldn't be replayed
+    pub history_error: Option<String>,
+
+    // Replay
+    pub playback: Option<Playback>,
+    /// The screen the replay was opened from, returned to on Esc
+    pub replay_back: Screen,
+
+    // Racing
+    /// The race being hosted or joined, if any
+    pub race: Option<Race>,
+
+    // Per-key stats
+    pub key_stats: KeyStats,
+    pub key_sort: KeySort,
 
-    // Timing
-    pub started_at: Option<Instant>,
-    pub finished_at: Option<Instant>,
+    // Themes
+    /// Presets followed by user themes
+    pub themes: Vec<Theme>,
+    /// Index into `themes` of the theme in use (or being previewed)
+    pub theme: usize,
+    /// The theme in use when the picker was opened, restored on cancel
+    pub theme_before_picker: usize,
 
-    // Live stats
-    pub wpm: f64,
-    pub accuracy: f64,
+    // Settings
+    pub config: Config,
+    pub settings_selected: usize,
+    /// Waiting for the key to bind to the selected row
+    pub settings_capture: bool,
+    /// Why the config couldn't be saved, or why a key couldn't be bound
+    pub settings_error: Option<String>,
 
     pub should_quit: bool,
 }
 
 impl App {
-    pub fn new() -> Self {
-        let language = Language::English;
-        let snippet = random_snippet(language);
-        let chars: Vec<char> = snippet.chars().collect();
-        let len = chars.len();
-        App {
+    pub fn new(library: Library, sources: SourceSnippets, words: Words, history: History, key_stats: KeyStats, themes: Vec<Theme>, config: Config) -> Self {
+        let option = MenuOption::Language(Language::English);
+        let snippet = library.random(option);
+        let mut app = App {
             screen: Screen::Menu,
             selected_menu: 0,
-            language,
+            menu: library.menu_options(),
+            option,
+            language: snippet.language,
+            mode: Mode::Snippet,
+            whitespace: WhitespacePolicy::Literal,
+            error_policy: ErrorPolicy::Off,
+            library,
+            sources,
+            words,
+            word_list: WordList::Top200,
+            word_options: WordOptions::default(),
+            session: Session::new(&snippet.text, Settings::default()),
+            ghost: None,
+            clock: Box::new(SystemClock::new()),
             snippet,
-            chars,
-            char_states: vec![CharState::Untyped; len],
-            cursor: 0,
-            errors: 0,
-            started_at: None,
-            finished_at: None,
-            wpm: 0.0,
-            accuracy: 100.0,
+            history,
+            history_sort: HistorySort::Newest,
+            history_filter: None,
+            history_scroll: 0,
+            save_error: None,
+            comparison: Comparison::default(),
+            recording: None,
+            history_error: None,
+            playback: None,
+            replay_back: Screen::Menu,
+            race: None,
+            key_stats,
+            key_sort: KeySort::Errors,
+            themes,
+            theme: 0,
+            theme_before_picker: 0,
+            config,
+            settings_selected: 0,
+            settings_capture: false,
+            settings_error: None,
             should_quit: false,
+        };
+        app.apply_config();
+        app
+    }
+
+    /// Makes the config's defaults the current selections
+    fn apply_config(&mut self) {
+        let language = MenuOption::Language(self.config.language);
+        self.selected_menu = self.menu.iter().position(|o| *o == language).unwrap_or(0);
+        self.mode = self.config.mode;
+        self.whitespace = self.config.whitespace;
+        self.error_policy = self.config.stop_on_error;
+        self.theme = self.themes.iter().position(|t| t.name == self.config.theme).unwrap_or(0);
+    }
+
+    fn save_config(&mut self) {
+        self.settings_error = self.config.save().err().map(|e| format!("not saved: {}", e));
+    }
+
+    /// A random snippet for the current menu option, preferring `--source`
+    /// files. Drills and words mode generate their text instead.
+    fn pick_snippet(&self) -> Snippet {
+        if self.option == MenuOption::Drill {
+            let count = match self.mode {
+                Mode::Words(count) => count,
+                _ => DRILL_WORDS,
+            };
+            return drill::generate(&self.key_stats, &self.words, &self.library, count);
+        }
+        if let Mode::Words(count) = self.mode {
+            return Snippet {
+                id: format!("words:{}/{}", count, self.word_list.label()),
+                title: self.word_list.label().to_string(),
+                language: Language::English,
+                difficulty: Default::default(),
+                tags: Vec::new(),
+                source: None,
+                text: self.words.generate(self.word_list, count, self.word_options),
+            };
+        }
+        let from_sources = match self.option {
+            MenuOption::Language(lang) => self.sources.random(lang),
+            MenuOption::Pack(_) | MenuOption::Drill => None,
+        };
+        from_sources.unwrap_or_else(|| self.library.random(self.option))
+    }
+
+    /// Starts typing `snippet` under the current mode and policies
+    pub fn start_snippet(&mut self, snippet: Snippet) {
+        let settings = self.session_settings();
+        self.start_under(snippet, settings);
+    }
+
+    /// The rules a session started now is typed under
+    fn session_settings(&self) -> Settings {
+        Settings {
+            whitespace: self.whitespace,
+            error_policy: self.error_policy,
+            allow_backspace: self.config.allow_backspace,
+            time_limit: self.mode.time_limit(),
+        }
+    }
+
+    /// Starts typing `snippet` under `settings`, which a race's host may
+    /// have picked
+    fn start_under(&mut self, snippet: Snippet, settings: Settings) {
+        self.snippet = snippet;
+        self.language = self.snippet.language;
+        self.session = Session::new(&self.snippet.text, settings);
+        self.ghost = self.pick_ghost();
+        self.extend_stream();
+        self.screen = Screen::Typing;
+    }
+
+    /// The ghost for a session on the current snippet: a replay of the
+    /// fastest recorded run on it
+    fn pick_ghost(&self) -> Option<Ghost> {
+        match self.config.ghost {
+            GhostMode::Off => None,
+            GhostMode::Best => {
+                // Other modes generate new text every time
+                if self.mode != Mode::Snippet || self.option == MenuOption::Drill {
+                    return None;
+                }
+                let history = &self.history;
+                let best = history.records.iter()
+                    .filter(|r| r.snippet_id == self.snippet.id && history.recording_of(r).is_some())
+                    .max_by(|a, b| a.wpm.total_cmp(&b.wpm))?;
+                let recording = history.recordings.get(history.recording_of(best)?).ok()?;
+                Some(Ghost::new(recording.replay()))
+            }
+        }
+    }
+
+    /// Characters the live cursor is ahead of the ghost, or of the pace
+    /// caret without one; negative when behind
+    pub fn lead(&self) -> Option<isize> {
+        let len = self.session.chars().len();
+        let rival = self.ghost.as_ref().map(|g| g.cursor().min(len)).or_else(|| self.pace_cursor())?;
+        Some(self.session.cursor() as isize - rival as isize)
+    }
+
+    /// Where a typist at the configured pace would be by now; it sets off
+    /// with the first keystroke
+    pub fn pace_cursor(&self) -> Option<usize> {
+        if self.config.pace_wpm == 0 {
+            return None;
         }
+        let chars = pace_chars(self.config.pace_wpm as f64, self.session.elapsed());
+        Some(chars.min(self.session.chars().len()))
     }
 
-    fn load_snippet(&mut self) {
-        self.snippet = random_snippet(self.language);
-        self.chars = self.snippet.chars().collect();
-        let len = self.chars.len();
-        self.char_states = vec![CharState::Untyped; len];
-        self.cursor = 0;
-        self.errors = 0;
-        self.started_at = None;
-        self.finished_at = None;
-        self.wpm = 0.0;
-        self.accuracy = 100.0;
+    /// In timed mode, appends more snippets whenever the untyped text
+    /// runs short, so the stream never ends before the timer does.
+    fn extend_stream(&mut self) {
+        if self.mode.time_limit().is_none() {
+            return;
+        }
+        while self.session.untyped() < STREAM_LOOKAHEAD {
+            // A pack can mix prose and code, so each snippet picks its own
+            let snippet = self.pick_snippet();
+            let separator = if snippet.language == Language::English { ' ' } else { '\n' };
+            let more = format!("{}{}", separator, snippet.text);
+            self.session.extend(&more);
+        }
     }
 
     pub fn menu_next(&mut self) {
-        self.selected_menu = (self.selected_menu + 1) % MENU_OPTIONS.len();
+        self.selected_menu = (self.selected_menu + 1) % self.menu.len();
     }
 
     pub fn menu_prev(&mut self) {
         if self.selected_menu == 0 {
-            self.selected_menu = MENU_OPTIONS.len() - 1;
+            self.selected_menu = self.menu.len() - 1;
         } else {
             self.selected_menu -= 1;
         }
     }
 
     pub fn start_session(&mut self) {
-        self.language = MENU_OPTIONS[self.selected_menu];
-        self.load_snippet();
-        self.screen = Screen::Typing;
+        self.option = self.menu[self.selected_menu];
+        let snippet = self.pick_snippet();
+        self.start_snippet(snippet);
     }
 
+    /// Starts a session straight away, as if `lang` had been picked from
+    /// the menu with `mode` selected; `None` keeps the configured default
+    pub fn start_with(&mut self, lang: Option<Language>, mode: Option<Mode>) {
+        if let Some(mode) = mode {
+            self.mode = mode;
+        }
+        if let Some(lang) = lang {
+            let option = MenuOption::Language(lang);
+            self.selected_menu = self.menu.iter().position(|o| *o == option).unwrap_or(0);
+        }
+        self.start_session();
+    }
+
+    /// Goes back to the menu, leaving any race
     pub fn go_to_menu(&mut self) {
+        self.race = None;
+        self.screen = Screen::Menu;
+    }
+
+    pub fn open_history(&mut self) {
+        self.history_scroll = 0;
+        self.history_error = None;
+        self.screen = Screen::History;
+    }
+
+    pub fn open_stats(&mut self) {
+        self.screen = Screen::Stats;
+    }
+
+    pub fn stats_next_sort(&mut self) {
+        self.key_sort = self.key_sort.next();
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.themes[self.theme]
+    }
+
+    pub fn open_themes(&mut self) {
+        self.theme_before_picker = self.theme;
+        self.screen = Screen::Themes;
+    }
+
+    /// Moves the picker selection, previewing it across the whole UI
+    pub fn theme_next(&mut self) {
+        self.theme = (self.theme + 1) % self.themes.len();
+    }
+
+    pub fn theme_prev(&mut self) {
+        self.theme = self.theme.checked_sub(1).unwrap_or(self.themes.len() - 1);
+    }
+
+    /// Keeps the previewed theme and makes it the default
+    pub fn theme_confirm(&mut self) {
+        self.config.theme = self.theme().name.clone();
+        self.save_config();
         self.screen = Screen::Menu;
     }
 
+    pub fn open_settings(&mut self) {
+        self.settings_selected = 0;
+        self.settings_capture = false;
+        self.screen = Screen::Settings;
+    }
+
+    pub fn settings_next(&mut self) {
+        self.settings_selected = (self.settings_selected + 1) % SETTINGS_ROWS;
+    }
+
+    pub fn settings_prev(&mut self) {
+        self.settings_selected = self.settings_selected.checked_sub(1).unwrap_or(SETTINGS_ROWS - 1);
+    }
+
+    /// Steps the selected setting to its next (or previous) value and
+    /// saves, carrying the change over to the menu's current selections.
+    /// On a key binding row it starts waiting for the new key.
+    pub fn settings_change(&mut self, forward: bool) {
+        let step = |i: usize, len: usize| if forward { (i + 1) % len } else { (i + len - 1) % len };
+        let Some(preference) = PREFERENCES.get(self.settings_selected) else {
+            self.settings_capture = true;
+            return;
+        };
+        let config = &mut self.config;
+        match preference {
+            Preference::Language => {
+                let i = LANGUAGES.iter().position(|l| *l == config.language).unwrap_or(0);
+                config.language = LANGUAGES[step(i, LANGUAGES.len())];
+                let language = MenuOption::Language(config.language);
+                self.selected_menu = self.menu.iter().position(|o| *o == language).unwrap_or(0);
+            }
+            Preference::Mode => {
+                let i = MODES.iter().position(|m| *m == config.mode).unwrap_or(0);
+                config.mode = MODES[step(i, MODES.len())];
+                self.mode = config.mode;
+            }
+            Preference::Theme => {
+                let i = self.themes.iter().position(|t| t.name == config.theme).unwrap_or(0);
+                self.theme = step(i, self.themes.len());
+                config.theme = self.themes[self.theme].name.clone();
+            }
+            Preference::Cursor => config.cursor = config.cursor.next(),
+            Preference::Whitespace => {
+                config.whitespace = config.whitespace.next();
+                self.whitespace = config.whitespace;
+            }
+            Preference::StopOnError => {
+                config.stop_on_error = config.stop_on_error.next();
+                self.error_policy = config.stop_on_error;
+            }
+            Preference::Backspace => config.allow_backspace = !config.allow_backspace,
+            Preference::ScrollContext => config.scroll_context = step(config.scroll_context, MAX_SCROLL_CONTEXT + 1),
+            Preference::Ghost => config.ghost = config.ghost.next(),
+            Preference::PaceCaret => {
+                let steps = (MAX_PACE_WPM / PACE_STEP + 1) as usize;
+                config.pace_wpm = step((config.pace_wpm / PACE_STEP) as usize, steps) as u32 * PACE_STEP;
+            }
+        }
+        self.save_config();
+    }
+
+    /// Binds `key` to the key binding row waiting for it. A key that's
+    /// already taken is refused and the row keeps waiting.
+    pub fn settings_bind(&mut self, key: char) {
+        match self.config.keys.set(self.settings_selected - PREFERENCES.len(), key) {
+            Ok(()) => {
+                self.settings_capture = false;
+                self.save_config();
+            }
+            Err(e) => self.settings_error = Some(e),
+        }
+    }
+
+    pub fn settings_cancel_capture(&mut self) {
+        self.settings_capture = false;
+        self.settings_error = None;
+    }
+
+    pub fn theme_cancel(&mut self) {
+        self.theme = self.theme_before_picker;
+        self.screen = Screen::Menu;
+    }
+
+    pub fn history_next_sort(&mut self) {
+        self.history_sort = self.history_sort.next();
+        self.history_scroll = 0;
+    }
+
+    /// Cycles the History filter: all → each language in menu order → all
+    pub fn history_next_filter(&mut self) {
+        self.history_filter = match self.history_filter {
+            None => LANGUAGES.first().copied(),
+            Some(lang) => {
+                let i = LANGUAGES.iter().position(|l| *l == lang).unwrap_or(0);
+                LANGUAGES.get(i + 1).copied()
+            }
+        };
+        self.history_scroll = 0;
+    }
+
+    pub fn history_scroll_down(&mut self) {
+        let rows = self.history.view(self.history_sort, self.history_filter).len();
+        if self.history_scroll + 1 < rows {
+            self.history_scroll += 1;
+        }
+    }
+
+    pub fn history_scroll_up(&mut self) {
+        self.history_scroll = self.history_scroll.saturating_sub(1);
+    }
+
+    /// Replays the selected (top) history entry, if it was recorded
+    pub fn history_replay(&mut self) {
+        let rows = self.history.view(self.history_sort, self.history_filter);
+        let Some(record) = rows.get(self.history_scroll) else { return };
+        let Some(id) = self.history.recording_of(record) else { return };
+        match self.history.recordings.get(id) {
+            Ok(recording) => self.open_replay(recording),
+            Err(e) => self.history_error = Some(format!("could not load the recording: {}", e)),
+        }
+    }
+
+    /// Replays the session that just finished
+    pub fn replay_last(&mut self) {
+        if let Some(recording) = self.recording.clone() {
+            self.open_replay(recording);
+        }
+    }
+
+    fn open_replay(&mut self, recording: Recording) {
+        self.playback = Some(Playback::new(recording, self.clock.now()));
+        self.replay_back = self.screen.clone();
+        self.screen = Screen::Replay;
+    }
+
+    pub fn close_replay(&mut self) {
+        self.playback = None;
+        self.screen = self.replay_back.clone();
+    }
+
+    pub fn replay_toggle_pause(&mut self) {
+        if let Some(playback) = &mut self.playback {
+            playback.toggle_pause();
+        }
+    }
+
+    pub fn replay_faster(&mut self) {
+        if let Some(playback) = &mut self.playback {
+            playback.faster();
+        }
+    }
+
+    pub fn replay_slower(&mut self) {
+        if let Some(playback) = &mut self.playback {
+            playback.slower();
+        }
+    }
+
+    pub fn replay_restart(&mut self) {
+        if let Some(playback) = &mut self.playback {
+            playback.restart();
+        }
+    }
+
+    pub fn cycle_mode(&mut self) {
+        let i = MODES.iter().position(|m| *m == self.mode).unwrap_or(0);
+        self.mode = MODES[(i + 1) % MODES.len()];
+    }
+
+    pub fn cycle_word_list(&mut self) {
+        self.word_list = self.word_list.next(&self.words);
+    }
+
+    pub fn toggle_punctuation(&mut self) {
+        self.word_options.punctuation = !self.word_options.punctuation;
+    }
+
+    pub fn toggle_numbers(&mut self) {
+        self.word_options.numbers = !self.word_options.numbers;
+    }
+
+    pub fn cycle_error_policy(&mut self) {
+        self.error_policy = self.error_policy.next();
+    }
+
+    pub fn cycle_whitespace(&mut self) {
+        self.whitespace = self.whitespace.next();
+    }
+
     pub fn quit(&mut self) {
         self.should_quit = true;
     }
 
     pub fn restart_session(&mut self) {
-        self.load_snippet();
-        self.screen = Screen::Typing;
+        let snippet = self.pick_snippet();
+        self.start_snippet(snippet);
     }
 
     pub fn new_snippet(&mut self) {
-        self.load_snippet();
-        self.screen = Screen::Typing;
+        let snippet = self.pick_snippet();
+        self.start_snippet(snippet);
     }
 
-    pub fn type_char(&mut self, c: char) {
-        if self.cursor >= self.chars.len() {
-            return;
-        }
-        if self.started_at.is_none() {
-            self.started_at = Some(Instant::now());
+    /// Hosts a race on a snippet in `lang`, or the configured language,
+    /// and waits in the Lobby for others to join
+    pub fn host_race(&mut self, mut race: Race, lang: Option<Language>) {
+        if let Some(lang) = lang {
+            let option = MenuOption::Language(lang);
+            self.selected_menu = self.menu.iter().position(|o| *o == option).unwrap_or(0);
         }
+        self.mode = Mode::Snippet;
+        self.option = self.menu[self.selected_menu];
+        self.snippet = self.pick_snippet();
+        self.language = self.snippet.language;
+        race.set_snippet(&self.snippet);
+        self.race = Some(race);
+        self.screen = Screen::Lobby;
+    }
 
-        if self.chars[self.cursor] == c {
-            self.char_states[self.cursor] = CharState::Correct;
-        } else {
-            self.char_states[self.cursor] = CharState::Wrong;
-            self.errors += 1;
-        }
-        self.cursor += 1;
-        self.update_stats();
+    /// Waits in the Lobby for the host of `race` to start it
+    pub fn join_race(&mut self, race: Race) {
+        self.mode = Mode::Snippet;
+        self.race = Some(race);
+        self.screen = Screen::Lobby;
+    }
 
-        if self.cursor == self.chars.len() {
-            self.finished_at = Some(Instant::now());
-            self.screen = Screen::Results;
+    pub fn open_lobby(&mut self) {
+        self.screen = Screen::Lobby;
+    }
+
+    /// Starts everyone in the race on the Lobby's snippet (host only)
+    pub fn race_start(&mut self) {
+        // Everyone races under the host's rules
+        let settings = self.session_settings();
+        let Some(race) = &mut self.race else { return };
+        if !race.is_host() {
+            return;
         }
+        race.start(settings);
+        self.start_under(self.snippet.clone(), settings);
     }
 
-    pub fn backspace(&mut self) {
-        if self.cursor == 0 {
+    /// Picks another snippet for the next race (host only)
+    pub fn race_new_snippet(&mut self) {
+        if !self.race.as_ref().is_some_and(|r| r.is_host()) {
             return;
         }
-        self.cursor -= 1;
-        if self.char_states[self.cursor] == CharState::Wrong {
-            self.errors = self.errors.saturating_sub(1);
+        self.snippet = self.pick_snippet();
+        self.language = self.snippet.language;
+        if let Some(race) = &mut self.race {
+            race.set_snippet(&self.snippet);
         }
-        self.char_states[self.cursor] = CharState::Untyped;
-        self.update_stats();
     }
 
-    pub fn tick(&mut self) {
-        if self.started_at.is_some() && self.finished_at.is_none() {
-            self.update_stats();
+    /// Takes in what the rest of the race sent since the last tick
+    fn poll_race(&mut self) {
+        let Some(race) = &mut self.race else { return };
+        for update in race.poll() {
+            match update {
+                Update::Snippet(snippet) => {
+                    self.option = MenuOption::Language(snippet.language);
+                    self.language = snippet.language;
+                    self.snippet = snippet;
+                }
+                // A late or repeated start mustn't throw away a race under way
+                Update::Start(settings) if self.screen == Screen::Lobby => {
+                    self.start_under(self.snippet.clone(), settings);
+                }
+                Update::Start(_) => {}
+            }
         }
     }
 
-    fn update_stats(&mut self) {
-        if let Some(start) = self.started_at {
-            let elapsed = start.elapsed().as_secs_f64();
-            if elapsed > 0.0 {
-                // WPM: chars typed / 5 (standard word length) / minutes
-                let correct_chars = self.char_states.iter()
-                    .filter(|s| **s == CharState::Correct)
-                    .count() as f64;
-                self.wpm = (correct_chars / 5.0) / (elapsed / 60.0);
+    fn report_race(&mut self) {
+        if let Some(race) = &mut self.race {
+            let session = &self.session;
+            // A failed session is out of the race, not placed in it
+            let finished = session.is_finished() && !session.failed();
+            race.report(session.progress(), session.wpm(), finished, session.failed());
+        }
+    }
+
+    /// Feeds a keypress to the session, stamped with the clock's time
+    pub fn key(&mut self, key: Key) {
+        self.session.key(key, self.clock.now());
+        self.extend_stream();
+        self.move_ghost();
+        self.check_finished();
+        self.report_race();
+    }
+
+    /// Moves time on for the live session or the replay, and hears from
+    /// the race
+    pub fn tick(&mut self) {
+        self.poll_race();
+        match self.screen {
+            Screen::Typing => {
+                self.session.tick(self.clock.now());
+                self.move_ghost();
+                self.check_finished();
+                self.report_race();
             }
-            let typed = self.cursor;
-            if typed > 0 {
-                self.accuracy = ((typed - self.errors) as f64 / typed as f64) * 100.0;
+            Screen::Replay => {
+                if let Some(playback) = &mut self.playback {
+                    playback.tick(self.clock.now());
+                }
             }
+            _ => {}
+        }
+    }
+
+    fn move_ghost(&mut self) {
+        if let Some(ghost) = &mut self.ghost {
+            ghost.advance_to(self.session.elapsed());
         }
     }
 
-    pub fn elapsed(&self) -> Duration {
-        match (self.started_at, self.finished_at) {
-            (Some(start), Some(end)) => end.duration_since(start),
-            (Some(start), None) => start.elapsed(),
-            _ => Duration::ZERO,
+    fn check_finished(&mut self) {
+        if self.screen == Screen::Typing && self.session.is_finished() {
+            self.finish();
+        }
+    }
+
+    /// Saves the finished session to history (unless it failed) and shows the results
+    fn finish(&mut self) {
+        let session = &self.session;
+        let snippet_id = match self.mode {
+            Mode::Snippet | Mode::Words(_) => self.snippet.id.clone(),
+            Mode::Timed(secs) if self.option == MenuOption::Drill => format!("time:{}/drill", secs),
+            Mode::Timed(secs) => format!("time:{}", secs),
+        };
+        let id = self.history.recordings.next_id(now_millis());
+        let record = SessionRecord {
+            timestamp: now_timestamp(),
+            language: self.language,
+            snippet_id,
+            wpm: session.wpm(),
+            raw_wpm: session.raw_wpm(),
+            accuracy: session.accuracy(),
+            errors: session.errors(),
+            corrected_errors: session.corrected_errors(),
+            consistency: session.consistency(),
+            elapsed: session.elapsed().as_secs_f64(),
+            chars: session.cursor(),
+            recording: Some(id),
+        };
+        let title = match self.mode {
+            Mode::Timed(_) => self.mode.label(),
+            Mode::Snippet | Mode::Words(_) => self.snippet.title.clone(),
+        };
+        let recording = Recording::new(id, record.timestamp, self.language, title, session);
+        self.key_stats.record(session.keystrokes());
+        // A failed session's speed isn't comparable, but its keystrokes still count
+        let saved = if session.failed() {
+            self.comparison = Comparison::default();
+            Ok(())
+        } else {
+            self.comparison = self.history.compare(&record);
+            self.history.append(record)
+                .and_then(|_| self.history.save_recording(&recording))
+        };
+        self.recording = Some(recording);
+        self.save_error = saved
+            .and_then(|_| self.key_stats.save())
+            .err()
+            .map(|e| e.to_string());
+        self.screen = Screen::Results;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rhonetyping::clock::ManualClock;
+
+    /// An app on the built-in content and default config, with nothing
+    /// read from or written to disk, running on `clock`
+    pub fn test_app(clock: &ManualClock) -> App {
+        let mut app = App::new(
+            Library::builtin(),
+            SourceSnippets::default(),
+            Words::builtin(),
+            History::default(),
+            KeyStats::default(),
+            Theme::presets(),
+            Config::default(),
+        );
+        app.clock = Box::new(clock.clone());
+        app
+    }
+
+    pub fn snippet(language: Language, text: &str) -> Snippet {
+        Snippet {
+            id: "test/snippet".to_string(),
+            title: "test".to_string(),
+            language,
+            difficulty: Default::default(),
+            tags: Vec::new(),
+            source: None,
+            text: text.to_string(),
         }
     }
 
-    pub fn progress(&self) -> f64 {
-        if self.chars.is_empty() { return 0.0; }
-        self.cursor as f64 / self.chars.len() as f64
+    /// Types `text` through the app, one key every `gap`
+    pub fn type_text(app: &mut App, clock: &ManualClock, text: &str, gap: Duration) {
+        for c in text.chars() {
+            app.key(Key::Char(c));
+            clock.advance(gap);
+        }
+    }
+
+    #[test]
+    fn finished_session_is_recorded() {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        app.start_snippet(snippet(Language::English, "the cat"));
+        type_text(&mut app, &clock, "thx cat", Duration::from_millis(500));
+
+        assert_eq!(app.screen, Screen::Results);
+        let record = app.history.records.last().expect("session recorded");
+        assert_eq!(record.snippet_id, "test/snippet");
+        assert_eq!(record.elapsed, 3.0);
+        assert_eq!(record.errors, 1);
+        assert_eq!(record.chars, 7);
+        assert!((record.wpm - 6.0 / 5.0 / (3.0 / 60.0)).abs() < 1e-9);
+        assert!((record.accuracy - 600.0 / 7.0).abs() < 1e-9);
+        assert_eq!(app.key_stats.keys["e"].misses, 1);
+    }
+
+    #[test]
+    fn timed_session_ends_on_a_tick() {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        app.start_snippet(snippet(Language::English, "the cat"));
+        type_text(&mut app, &clock, "the cat", Duration::from_millis(100));
+        app.mode = Mode::Timed(15);
+        app.start_snippet(snippet(Language::English, "the cat"));
+        type_text(&mut app, &clock, "the cat ", Duration::from_secs(1));
+        // The stream was topped up past the snippet's end
+        assert_eq!(app.screen, Screen::Typing);
+
+        clock.set(Duration::from_secs(20));
+        app.tick();
+        assert_eq!(app.screen, Screen::Results);
+        assert_eq!(app.session.elapsed(), Duration::from_secs(15));
+        assert_eq!(app.history.records.last().unwrap().snippet_id, "time:15");
+        // The faster snippet run isn't a timed best
+        assert_eq!(app.comparison.best_wpm, None);
+    }
+
+    #[test]
+    fn timed_stream_joins_each_snippet_by_its_own_language() {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        app.mode = Mode::Timed(15);
+        // The menu is on English, so the stream goes on in prose
+        app.start_snippet(snippet(Language::Rust, "fn main() {}"));
+        assert_eq!(app.session.chars()[12], ' ');
+    }
+
+    #[test]
+    fn bindings_refuse_keys_already_in_use() {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        app.open_settings();
+        app.settings_selected = PREFERENCES.len() + 1; // mode
+        app.settings_change(true);
+        assert!(app.settings_capture);
+
+        app.settings_bind('j');
+        assert_eq!(app.settings_error.as_deref(), Some("'j' is already used on the menu"));
+        app.settings_bind('h');
+        assert_eq!(app.settings_error.as_deref(), Some("'h' is already bound to history"));
+        assert!(app.settings_capture);
+        assert_eq!(app.config.keys.mode, 'm');
+
+        // Retry only shares the results screen, so menu keys are free for it
+        assert_eq!(app.config.keys.set(11, 'm'), Ok(()));
+        assert!(app.config.keys.set(11, 'v').is_err());
+        assert!(app.config.keys.set(KeyBindings::COUNT, 'z').is_err());
+        assert_eq!(app.config.keys.replay, 'v');
+
+        // A loaded config is held to the same rules
+        assert_eq!(KeyBindings::default().check(), Ok(()));
+        let clashing = KeyBindings { history: 'k', ..KeyBindings::default() };
+        assert_eq!(clashing.check(), Err("'k' is already used on the menu".to_string()));
+    }
+
+    #[test]
+    fn sudden_death_isnt_recorded() {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        app.error_policy = ErrorPolicy::SuddenDeath;
+        app.start_snippet(snippet(Language::English, "the cat"));
+        type_text(&mut app, &clock, "thx ", Duration::from_millis(200));
+
+        assert_eq!(app.screen, Screen::Results);
+        assert!(app.session.failed());
+        assert!(app.history.records.is_empty());
+    }
+
+    #[test]
+    fn finished_session_replays_in_real_time() {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        app.start_snippet(snippet(Language::English, "abcd"));
+        type_text(&mut app, &clock, "abxd", Duration::from_secs(1));
+        assert_eq!(app.screen, Screen::Results);
+
+        app.replay_last();
+        assert_eq!(app.screen, Screen::Replay);
+        clock.advance(Duration::from_millis(1500));
+        app.tick();
+        let playback = app.playback.as_ref().unwrap();
+        assert_eq!(playback.replay.session().cursor(), 2);
+
+        // Twice as fast: another 1.5s of playback in 750ms
+        app.replay_faster();
+        clock.advance(Duration::from_millis(750));
+        app.tick();
+        let playback = app.playback.as_ref().unwrap();
+        assert_eq!(playback.position, Duration::from_secs(3));
+        assert!(playback.replay.is_done());
+        assert_eq!(playback.replay.session().states(), app.session.states());
+
+        app.close_replay();
+        assert_eq!(app.screen, Screen::Results);
+    }
+
+    #[test]
+    fn pace_caret_sets_the_lead_without_a_ghost() {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        assert_eq!(app.lead(), None);
+        // 60 wpm is 5 characters a second
+        app.config.pace_wpm = 60;
+        app.start_snippet(snippet(Language::English, "the quick brown fox jumps"));
+        assert_eq!(app.lead(), Some(0));
+
+        type_text(&mut app, &clock, "the", Duration::from_secs(1));
+        assert_eq!(app.pace_cursor(), Some(10));
+        assert_eq!(app.lead(), Some(-7));
+    }
+
+    #[test]
+    fn best_ghost_replays_the_fastest_run() {
+        let dir = std::env::temp_dir().join(format!("rhonetyping-ghost-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        app.history = History::open(Some(dir.clone()));
+        app.config.ghost = GhostMode::Best;
+
+        app.start_snippet(snippet(Language::English, "abcdef"));
+        assert!(app.ghost.is_none());
+        type_text(&mut app, &clock, "abcdef", Duration::from_secs(1));
+        assert_eq!(app.screen, Screen::Results);
+
+        // Twice as fast as the recorded run
+        app.start_snippet(snippet(Language::English, "abcdef"));
+        type_text(&mut app, &clock, "abcd", Duration::from_millis(500));
+        assert_eq!(app.ghost.as_ref().unwrap().cursor(), 2);
+        assert_eq!(app.lead(), Some(2));
+
+        // Other snippets have no best to race
+        app.start_snippet(Snippet { id: "test/other".to_string(), ..snippet(Language::English, "abcdef") });
+        assert!(app.ghost.is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn pace_caret_waits_for_the_first_key() {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        app.config.pace_wpm = 120;
+        app.start_snippet(snippet(Language::English, "the quick brown fox"));
+        clock.advance(Duration::from_secs(5));
+        app.tick();
+        assert_eq!(app.pace_cursor(), Some(0));
+
+        // 120 wpm is 10 characters a second, and it stops at the end
+        type_text(&mut app, &clock, "th", Duration::from_millis(500));
+        assert_eq!(app.pace_cursor(), Some(5));
+        clock.advance(Duration::from_secs(5));
+        app.tick();
+        assert_eq!(app.pace_cursor(), Some(19));
+
+        app.config.pace_wpm = 0;
+        assert_eq!(app.pace_cursor(), None);
+    }
+
+    #[test]
+    fn race_starts_everyone_on_the_hosts_snippet() {
+        let clock = ManualClock::new();
+        let mut host = test_app(&clock);
+        host.host_race(Race::host(0, "ana".to_string()).unwrap(), Some(Language::Python));
+        let address = format!("127.0.0.1:{}", host.race.as_ref().unwrap().address);
+        let mut joiner = test_app(&clock);
+        joiner.join_race(Race::join(&address, "bo".to_string()).unwrap());
+        assert_eq!(joiner.screen, Screen::Lobby);
+
+        // Ticks both apps until `done` holds
+        let settle = |host: &mut App, joiner: &mut App, done: &dyn Fn(&App, &App) -> bool| {
+            for _ in 0..1000 {
+                host.tick();
+                joiner.tick();
+                if done(host, joiner) {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            panic!("race never settled");
+        };
+        settle(&mut host, &mut joiner, &|h, j| {
+            j.race.as_ref().unwrap().has_snippet() && h.race.as_ref().unwrap().racers.len() == 2
+        });
+        assert_eq!(joiner.snippet.text, host.snippet.text);
+        assert_eq!(joiner.language, Language::Python);
+
+        // Only the host can start it
+        joiner.race_start();
+        assert_eq!(joiner.screen, Screen::Lobby);
+        host.error_policy = ErrorPolicy::Letter;
+        host.race_start();
+        assert_eq!(host.screen, Screen::Typing);
+        settle(&mut host, &mut joiner, &|_, j| j.screen == Screen::Typing);
+        // The joiner types under the host's rules, not its own
+        assert_eq!(joiner.session.settings().error_policy, ErrorPolicy::Letter);
+
+        let text = joiner.snippet.text.clone();
+        type_text(&mut joiner, &clock, &text, Duration::from_millis(100));
+        assert_eq!(joiner.screen, Screen::Results);
+        settle(&mut host, &mut joiner, &|h, _| h.race.as_ref().unwrap().racers.iter().any(|r| r.place == Some(1)));
+
+        // A start that arrives after the lobby leaves the results alone
+        host.race.as_mut().unwrap().start(Settings::default());
+        settle(&mut host, &mut joiner, &|_, j| j.race.as_ref().unwrap().racers.iter().all(|r| r.place.is_none()));
+        assert_eq!(joiner.screen, Screen::Results);
+
+        joiner.go_to_menu();
+        assert!(joiner.race.is_none());
+        settle(&mut host, &mut joiner, &|h, _| h.race.as_ref().unwrap().racers.len() == 1);
     }
 }
@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process;
+
+use crate::app::Mode;
+use crate::history::{History, ROLLING_WINDOW, SessionRecord, format_timestamp};
+use crate::keystats::{KeySort, KeyStats};
+use crate::snippets::{LANGUAGES, Language};
+
+pub const USAGE: &str = "\
+usage: rhonetyping [options]              open the menu, or start a session if --lang/--mode is given
+       rhonetyping file <path> [options]  practise snippets from a file
+       rhonetyping host [options]         host a race on the local network
+       rhonetyping join <addr> [options]  join a race hosted at <addr>, host or host:port
+       rhonetyping stats                  print a summary of past sessions
+       rhonetyping export [--format csv|json]
+                                          write the session history to stdout
+
+options:
+  -s, --source <file-or-dir>  practise on your own files (repeatable)
+  -l, --lang <language>       english, rust or python
+  -m, --mode <mode>           snippet, time:<secs> or words:<count>
+  -p, --port <port>           port to host a race on (default 7340)
+  -n, --name <name>           what other racers see you as (default $USER)
+  -h, --help                  show this help";
+
+/// Session options shared by the TUI commands
+#[derive(Debug, Default)]
+pub struct Options {
+    pub sources: Vec<PathBuf>,
+    pub lang: Option<Language>,
+    pub mode: Option<Mode>,
+    /// Race options, only taken by `host` and `join`
+    pub port: Option<u16>,
+    pub name: Option<String>,
+}
+
+impl Options {
+    /// Whether to skip the menu and start typing right away
+    pub fn starts_session(&self) -> bool {
+        self.lang.is_some() || self.mode.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+pub enum Command {
+    Run(Options),
+    Host(Options),
+    /// Join the race at the address
+    Join(String, Options),
+    Stats,
+    Export(ExportFormat),
+}
+
+/// Parses the command line, exiting with the usage on anything invalid
+pub fn parse_args() -> Command {
+    let mut args = std::env::args().skip(1).peekable();
+    let command = args.peek().cloned();
+    match command.as_deref() {
+        Some("stats") => {
+            args.next();
+            if let Some(arg) = args.next() {
+                fail(&format!("unexpected argument: {}", arg));
+            }
+            Command::Stats
+        }
+        Some("export") => {
+            args.next();
+            let mut format = ExportFormat::Csv;
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--format" | "-f" => format = match value(&mut args, &arg).as_str() {
+                        "csv" => ExportFormat::Csv,
+                        "json" => ExportFormat::Json,
+                        other => fail(&format!("unknown export format: {}", other)),
+                    },
+                    _ => fail(&format!("unknown argument: {}", arg)),
+                }
+            }
+            Command::Export(format)
+        }
+        Some("file") => {
+            args.next();
+            let path = args.next().unwrap_or_else(|| fail("file needs a path"));
+            let lang = PathBuf::from(&path).extension()
+                .and_then(|e| e.to_str())
+                .and_then(Language::from_extension)
+                .unwrap_or_else(|| fail(&format!("don't know what language {} is", path)));
+            let mut options = parse_options(args);
+            // Any --source files join it
+            options.sources.insert(0, PathBuf::from(path));
+            options.lang = Some(lang);
+            Command::Run(options)
+        }
+        Some("host") => {
+            args.next();
+            let options = parse_options(args);
+            if options.mode.is_some() {
+                fail("races are always on one snippet; --mode doesn't apply");
+            }
+            Command::Host(options)
+        }
+        Some("join") => {
+            args.next();
+            let address = args.next().unwrap_or_else(|| fail("join needs the host's address"));
+            let options = parse_options(args);
+            if options.starts_session() || !options.sources.is_empty() || options.port.is_some() {
+                fail("the host picks the snippet; join only takes --name");
+            }
+            Command::Join(address, options)
+        }
+        _ => {
+            let options = parse_options(args);
+            if options.port.is_some() || options.name.is_some() {
+                fail("--port and --name are for host and join");
+            }
+            Command::Run(options)
+        }
+    }
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Options {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--source" | "-s" => options.sources.push(PathBuf::from(value(&mut args, &arg))),
+            "--lang" | "-l" => {
+                let name = value(&mut args, &arg);
+                options.lang = Some(Language::from_name(&name)
+                    .unwrap_or_else(|| fail(&format!("unknown language: {}", name))));
+            }
+            "--mode" | "-m" => {
+                let text = value(&mut args, &arg);
+                options.mode = Some(Mode::parse(&text)
+                    .unwrap_or_else(|| fail(&format!("unknown mode: {}", text))));
+            }
+            "--port" | "-p" => {
+                let text = value(&mut args, &arg);
+                options.port = Some(text.parse().unwrap_or_else(|_| fail(&format!("invalid port: {}", text))));
+            }
+            "--name" | "-n" => options.name = Some(value(&mut args, &arg)),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            other => fail(&format!("unknown argument: {}", other)),
+        }
+    }
+    options
+}
+
+fn value(args: &mut impl Iterator<Item = String>, flag: &str) -> String {
+    args.next().unwrap_or_else(|| fail(&format!("{} needs a value", flag)))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
+
+/// Prints totals per language and kind of session, so each best matches
+/// what the results screen counts as a record, then the weakest keys
+pub fn print_stats(history: &History, key_stats: &KeyStats) {
+    if history.records.is_empty() {
+        println!("no sessions yet");
+        return;
+    }
+    println!("{} sessions\n", history.records.len());
+    println!("{:<8}  {:<16}  {:>8}  {:>8}  {:>8}  {:>8}", "language", "kind", "sessions", "best wpm",
+        format!("avg({})", ROLLING_WINDOW), "avg acc");
+    for lang in LANGUAGES {
+        let mut kinds: BTreeMap<&str, Vec<&SessionRecord>> = BTreeMap::new();
+        for record in history.records.iter().filter(|r| r.language == *lang) {
+            kinds.entry(record.kind()).or_default().push(record);
+        }
+        for (kind, records) in kinds {
+            let recent = &records[records.len().saturating_sub(ROLLING_WINDOW)..];
+            let best = records.iter().map(|r| r.wpm).fold(0.0, f64::max);
+            let avg_wpm = recent.iter().map(|r| r.wpm).sum::<f64>() / recent.len() as f64;
+            let avg_acc = recent.iter().map(|r| r.accuracy).sum::<f64>() / recent.len() as f64;
+            println!("{:<8}  {:<16}  {:>8}  {:>8.0}  {:>8.0}  {:>7.1}%",
+                lang.label(), kind, records.len(), best, avg_wpm, avg_acc);
+        }
+    }
+
+    let weakest = key_stats.ranked(false, KeySort::Errors);
+    if !weakest.is_empty() {
+        let keys: Vec<String> = weakest.iter()
+            .take(5)
+            .map(|(key, stat)| format!("{:?} {:.1}%", key, stat.error_rate()))
+            .collect();
+        println!("\nweakest keys: {}", keys.join(", "));
+    }
+}
+
+/// Writes the whole history to stdout
+pub fn export(history: &History, format: ExportFormat) {
+    match format {
+        ExportFormat::Json => {
+            let json = serde_json::to_string_pretty(&history.records).expect("history serializes");
+            println!("{}", json);
+        }
+        ExportFormat::Csv => {
+            println!("timestamp,date,language,snippet_id,wpm,raw_wpm,accuracy,errors,corrected_errors,elapsed,chars,consistency");
+            for r in &history.records {
+                println!(
+                    "{},{},{},{},{:.2},{:.2},{:.2},{},{},{:.2},{},{}",
+                    r.timestamp,
+                    format_timestamp(r.timestamp),
+                    r.language.label().to_lowercase(),
+                    csv_field(&r.snippet_id),
+                    r.wpm,
+                    r.raw_wpm,
+                    r.accuracy,
+                    r.errors,
+                    r.corrected_errors,
+                    r.elapsed,
+                    r.chars,
+                    r.consistency.map_or(String::new(), |c| format!("{:.1}", c)),
+                );
+            }
+        }
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
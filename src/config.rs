@@ -0,0 +1,273 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use rhonetyping::engine::{ErrorPolicy, WhitespacePolicy};
+
+use crate::app::{Mode, SCROLL_CONTEXT};
+use crate::ghost::GhostMode;
+use crate::paths;
+use crate::snippets::Language;
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// User preferences, stored as `config.toml` in the XDG config directory.
+/// Missing keys take their defaults, so a partial file is fine:
+///
+/// ```toml
+/// language = "rust"          # english | rust | python
+/// mode = "time:60"           # snippet | time:<secs> | words:<count>
+/// theme = "solarized"        # a preset or a user theme's name
+/// cursor = "underline"       # block | underline | bar
+/// whitespace = "auto-indent" # literal | skip-indent | auto-indent
+/// stop_on_error = "word"     # off | letter | word | sudden-death
+/// allow_backspace = false    # hardcore: mistakes can't be deleted
+/// scroll_context = 2
+/// ghost = "best"             # off | best
+/// pace_wpm = 70              # 0 hides the pace caret
+///
+/// [keys]
+/// quit = "q"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Language selected in the menu at startup
+    pub language: Language,
+    pub mode: Mode,
+    pub theme: String,
+    pub cursor: CursorStyle,
+    pub whitespace: WhitespacePolicy,
+    /// What a wrong key does
+    pub stop_on_error: ErrorPolicy,
+    /// Whether Backspace and word delete work at all
+    pub allow_backspace: bool,
+    /// Rows kept visible around the cursor when the snippet scrolls
+    pub scroll_context: usize,
+    /// Whether sessions race the personal best
+    pub ghost: GhostMode,
+    /// Speed of the pace caret; 0 for none
+    pub pace_wpm: u32,
+    pub keys: KeyBindings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            language: Language::English,
+            mode: Mode::Snippet,
+            theme: "dark".to_string(),
+            cursor: CursorStyle::Block,
+            whitespace: WhitespacePolicy::Literal,
+            stop_on_error: ErrorPolicy::Off,
+            allow_backspace: true,
+            scroll_context: SCROLL_CONTEXT,
+            ghost: GhostMode::Off,
+            pace_wpm: 0,
+            keys: KeyBindings::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file. A missing file gives the defaults; a broken
+    /// one gives the defaults plus a warning.
+    pub fn load() -> (Config, Option<String>) {
+        let Some(path) = config_path() else { return (Config::default(), None) };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return (Config::default(), None),
+            Err(e) => return (Config::default(), Some(format!("could not read {}: {}", path.display(), e))),
+        };
+        match toml::from_str::<Config>(&text) {
+            // Clashing bindings would leave an action out of reach
+            Ok(mut config) => match config.keys.check() {
+                Ok(()) => (config, None),
+                Err(e) => {
+                    config.keys = KeyBindings::default();
+                    (config, Some(format!("ignored [keys] in {}: {}", path.display(), e)))
+                }
+            },
+            Err(e) => (Config::default(), Some(format!("ignored {}: {}", path.display(), e.message()))),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = config_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory (HOME unset)"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    paths::config_dir().map(|d| d.join(CONFIG_FILE))
+}
+
+/// How the typing cursor is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorStyle {
+    /// The character under the cursor in reverse colours
+    Block,
+    /// The character under the cursor underlined in the accent colour
+    Underline,
+    /// The terminal's own cursor, as a bar before the character
+    Bar,
+}
+
+impl CursorStyle {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CursorStyle::Block => "block",
+            CursorStyle::Underline => "underline",
+            CursorStyle::Bar => "bar",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            CursorStyle::Block => CursorStyle::Underline,
+            CursorStyle::Underline => CursorStyle::Bar,
+            CursorStyle::Bar => CursorStyle::Block,
+        }
+    }
+}
+
+/// Single-key shortcuts on the menu and results screens
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub quit: char,
+    pub mode: char,
+    pub whitespace: char,
+    pub errors: char,
+    pub word_list: char,
+    pub punctuation: char,
+    pub numbers: char,
+    pub history: char,
+    pub stats: char,
+    pub theme: char,
+    pub settings: char,
+    pub retry: char,
+    pub new_snippet: char,
+    pub replay: char,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            quit: 'q',
+            mode: 'm',
+            whitespace: 'w',
+            errors: 'e',
+            word_list: 'l',
+            punctuation: 'p',
+            numbers: 'n',
+            history: 'h',
+            stats: 's',
+            theme: 't',
+            settings: 'o',
+            retry: 'r',
+            new_snippet: 'n',
+            replay: 'v',
+        }
+    }
+}
+
+/// The screens bindings are used on: the labels (from `KeyBindings::all`)
+/// of the bindings each one handles, and the keys it keeps for itself
+const SCREENS: &[(&str, &[&str], &[char])] = &[
+    (
+        "menu",
+        &["quit", "mode", "whitespace", "stop on error", "word list", "punctuation", "numbers",
+          "history", "stats", "theme", "settings"],
+        &['j', 'k', ' '],
+    ),
+    ("results screen", &["quit", "retry", "new snippet", "replay"], &[]),
+    ("lobby", &["new snippet"], &[' ', 'q']),
+];
+
+impl KeyBindings {
+    pub const COUNT: usize = 14;
+
+    /// Every binding with its settings label, in display order
+    pub fn all(&self) -> [(&'static str, char); KeyBindings::COUNT] {
+        [
+            ("quit", self.quit),
+            ("mode", self.mode),
+            ("whitespace", self.whitespace),
+            ("stop on error", self.errors),
+            ("word list", self.word_list),
+            ("punctuation", self.punctuation),
+            ("numbers", self.numbers),
+            ("history", self.history),
+            ("stats", self.stats),
+            ("theme", self.theme),
+            ("settings", self.settings),
+            ("retry", self.retry),
+            ("new snippet", self.new_snippet),
+            ("replay", self.replay),
+        ]
+    }
+
+    /// Binds `key` to the binding at `index`, unless that would take a key
+    /// from another binding or from the screen the binding is used on
+    pub fn set(&mut self, index: usize, key: char) -> Result<(), String> {
+        if index >= KeyBindings::COUNT {
+            return Err(format!("no key binding {}", index));
+        }
+        if let Some(conflict) = self.conflict(index, key) {
+            return Err(conflict);
+        }
+        let slot = match index {
+            0 => &mut self.quit,
+            1 => &mut self.mode,
+            2 => &mut self.whitespace,
+            3 => &mut self.errors,
+            4 => &mut self.word_list,
+            5 => &mut self.punctuation,
+            6 => &mut self.numbers,
+            7 => &mut self.history,
+            8 => &mut self.stats,
+            9 => &mut self.theme,
+            10 => &mut self.settings,
+            11 => &mut self.retry,
+            12 => &mut self.new_snippet,
+            13 => &mut self.replay,
+            _ => unreachable!("checked against COUNT above"),
+        };
+        *slot = key;
+        Ok(())
+    }
+
+    /// Checks that no two bindings on a screen share a key and none takes
+    /// a key the screen keeps for itself
+    pub fn check(&self) -> Result<(), String> {
+        self.all().iter().enumerate()
+            .find_map(|(i, (_, key))| self.conflict(i, *key))
+            .map_or(Ok(()), Err)
+    }
+
+    /// Why binding `key` at `index` would clash, if it would
+    fn conflict(&self, index: usize, key: char) -> Option<String> {
+        let all = self.all();
+        let label = all[index].0;
+        for (screen, labels, reserved) in SCREENS.iter().filter(|(_, l, _)| l.contains(&label)) {
+            if reserved.contains(&key) {
+                return Some(format!("{:?} is already used on the {}", key, screen));
+            }
+            let taken = all.iter().enumerate()
+                .find(|(i, (other, k))| *i != index && *k == key && labels.contains(other));
+            if let Some((_, (other, _))) = taken {
+                return Some(format!("{:?} is already bound to {}", key, other));
+            }
+        }
+        None
+    }
+}
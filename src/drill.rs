@@ -0,0 +1,88 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::thread_rng;
+
+use crate::keystats::{KeyStat, KeyStats, MIN_SAMPLES};
+use crate::snippets::{Language, Library, Snippet};
+use crate::words::{WordList, WordOptions, Words};
+
+/// How many keys and bigrams a drill focuses on at once
+const MAX_TARGETS: usize = 5;
+/// Keys scoring below this are considered learned and leave the focus set
+const WEAKNESS_THRESHOLD: f64 = 0.25;
+/// Words per drill when the mode doesn't say otherwise
+pub const DRILL_WORDS: usize = 30;
+
+/// The keys and bigrams currently most in need of practice, weakest first,
+/// each paired with how weak it is
+pub fn targets(stats: &KeyStats) -> Vec<(String, f64)> {
+    let all = stats.keys.values().chain(stats.bigrams.values());
+    let (sum, samples) = all.fold((0.0, 0), |(sum, n), s| (sum + s.latency_ms, n + s.latency_samples));
+    let mean_latency = if samples > 0 { sum / samples as f64 } else { 0.0 };
+
+    let mut targets: Vec<(String, f64)> = stats.keys.iter()
+        .chain(&stats.bigrams)
+        .filter(|(key, stat)| stat.count() >= MIN_SAMPLES && !key.contains(char::is_whitespace))
+        .map(|(key, stat)| (key.clone(), weakness(stat, mean_latency)))
+        .filter(|(_, score)| *score > WEAKNESS_THRESHOLD)
+        .collect();
+    targets.sort_by(|a, b| b.1.total_cmp(&a.1));
+    targets.truncate(MAX_TARGETS);
+    targets
+}
+
+/// Error rate counts heavily; latency only counts where it's slower than
+/// the user's overall average
+fn weakness(stat: &KeyStat, mean_latency: f64) -> f64 {
+    let errors = stat.error_rate() / 100.0 * 4.0;
+    let slowness = match stat.mean_latency() {
+        Some(ms) if mean_latency > 0.0 => (ms / mean_latency - 1.0).max(0.0),
+        _ => 0.0,
+    };
+    errors + slowness
+}
+
+/// Builds a drill of `count` words and code tokens that contain the
+/// current targets, each picked with a weight proportional to how weak
+/// its targets are. As keys improve their weight drops until they fall
+/// out of the focus set and the next-weakest ones take over; with nothing
+/// left to work on (or no stats yet) the drill is plain common words.
+pub fn generate(stats: &KeyStats, words: &Words, library: &Library, count: usize) -> Snippet {
+    let targets = targets(stats);
+
+    let code_tokens = library.builtin.snippets.iter()
+        .chain(library.packs.iter().flat_map(|p| &p.snippets))
+        .flat_map(|s| s.text.split_whitespace());
+    let mut candidates: Vec<&str> = words.ranked().iter().map(String::as_str).chain(code_tokens).collect();
+    candidates.sort_unstable();
+    candidates.dedup();
+
+    let weighted: Vec<(&str, f64)> = candidates.into_iter()
+        .map(|token| {
+            let score = targets.iter()
+                .filter(|(target, _)| token.contains(target.as_str()))
+                .map(|(_, weakness)| weakness)
+                .sum();
+            (token, score)
+        })
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+
+    let text = match WeightedIndex::new(weighted.iter().map(|(_, score)| *score)) {
+        Ok(dist) => {
+            let mut rng = thread_rng();
+            (0..count).map(|_| weighted[dist.sample(&mut rng)].0).collect::<Vec<_>>().join(" ")
+        }
+        Err(_) => words.generate(WordList::Top200, count, WordOptions::default()),
+    };
+
+    let focus: Vec<&str> = targets.iter().map(|(t, _)| t.as_str()).collect();
+    Snippet {
+        id: "drill".to_string(),
+        title: if focus.is_empty() { "warm-up".to_string() } else { focus.join(" ") },
+        language: Language::English,
+        difficulty: Default::default(),
+        tags: Vec::new(),
+        source: None,
+        text,
+    }
+}
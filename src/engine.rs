@@ -0,0 +1,671 @@
+//! The typing engine: the text being typed, what happened to each
+//! character, the cursor, timing and live stats.
+//!
+//! Nothing in here reads a clock. Every input comes with a timestamp, a
+//! [`Duration`] since any fixed point the frontend picks (when the session
+//! was loaded, say), and the engine only ever compares timestamps with each
+//! other. A session is driven by [`Session::key`] and [`Session::tick`]:
+//!
+//! ```
+//! use std::time::Duration;
+//! use rhonetyping::engine::{Key, Session, Settings};
+//!
+//! let mut session = Session::new("hi", Settings::default());
+//! session.key(Key::Char('h'), Duration::from_millis(0));
+//! session.key(Key::Char('i'), Duration::from_millis(600));
+//! assert!(session.is_finished());
+//! assert_eq!(session.wpm().round(), 40.0);
+//! ```
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CharState {
+    Untyped,
+    Correct,
+    Wrong,
+}
+
+/// How leading whitespace on code lines is handled while typing.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WhitespacePolicy {
+    /// Every indentation character must be typed (Tab types one indent unit).
+    Literal,
+    /// Leading whitespace is filled in automatically after Enter.
+    SkipIndent,
+    /// Enter carries over the previous line's indentation, like an editor;
+    /// any extra indentation still has to be typed.
+    AutoIndent,
+}
+
+impl WhitespacePolicy {
+    pub fn label(&self) -> &'static str {
+        match self {
+            WhitespacePolicy::Literal => "literal",
+            WhitespacePolicy::SkipIndent => "skip indent",
+            WhitespacePolicy::AutoIndent => "auto indent",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            WhitespacePolicy::Literal => WhitespacePolicy::SkipIndent,
+            WhitespacePolicy::SkipIndent => WhitespacePolicy::AutoIndent,
+            WhitespacePolicy::AutoIndent => WhitespacePolicy::Literal,
+        }
+    }
+}
+
+/// What happens on a wrong key
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorPolicy {
+    /// Mistakes are marked and typing carries on
+    Off,
+    /// A wrong key doesn't advance; the right one has to be typed
+    Letter,
+    /// Typing carries on within a word, but the space or newline after it
+    /// can't be typed until every mistake in the word is fixed. With
+    /// backspace off nothing could fix them, so the word's mistakes stay.
+    Word,
+    /// The session fails as soon as a word is finished with a mistake in it
+    SuddenDeath,
+}
+
+impl ErrorPolicy {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ErrorPolicy::Off => "off",
+            ErrorPolicy::Letter => "stop on letter",
+            ErrorPolicy::Word => "stop on word",
+            ErrorPolicy::SuddenDeath => "sudden death",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ErrorPolicy::Off => ErrorPolicy::Letter,
+            ErrorPolicy::Letter => ErrorPolicy::Word,
+            ErrorPolicy::Word => ErrorPolicy::SuddenDeath,
+            ErrorPolicy::SuddenDeath => ErrorPolicy::Off,
+        }
+    }
+}
+
+/// One keypress during a session
+#[derive(Debug, Clone, Copy)]
+pub struct Keystroke {
+    /// Time since the session's first keystroke
+    pub at: Duration,
+    /// Position in the text the key was typed at
+    pub index: usize,
+    pub expected: char,
+    pub typed: char,
+}
+
+/// Speed over one second of a session, for the results chart
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    /// Seconds since the first keystroke at the end of this sample
+    pub time: f64,
+    /// Net WPM of the session so far
+    pub wpm: f64,
+    /// Raw WPM within this second alone
+    pub raw_wpm: f64,
+    /// Mistyped keys within this second
+    pub errors: usize,
+}
+
+/// An input to the engine
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Key {
+    /// A printable character; Enter is `Char('\n')`
+    Char(char),
+    /// Types one indentation unit, as in an editor
+    Tab,
+    Backspace,
+    /// Deletes back to the start of the previous word
+    DeleteWord,
+}
+
+/// One key as it was fed to a session
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Input {
+    /// Time since the session's first keystroke
+    pub at: Duration,
+    pub key: Key,
+}
+
+/// The rules a session is typed under
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Settings {
+    pub whitespace: WhitespacePolicy,
+    pub error_policy: ErrorPolicy,
+    /// Whether Backspace and word delete work at all
+    pub allow_backspace: bool,
+    /// Ends the session this long after the first keystroke
+    pub time_limit: Option<Duration>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            whitespace: WhitespacePolicy::Literal,
+            error_policy: ErrorPolicy::Off,
+            allow_backspace: true,
+            time_limit: None,
+        }
+    }
+}
+
+/// One typing session over a text
+#[derive(Debug, Clone)]
+pub struct Session {
+    settings: Settings,
+    chars: Vec<char>,
+    states: Vec<CharState>,
+    /// Characters filled in by the whitespace policy rather than typed;
+    /// excluded from WPM and accuracy.
+    auto_filled: Vec<bool>,
+    /// Positions where stop-on-error held the cursor on a miss that hasn't
+    /// been fixed yet
+    held: Vec<bool>,
+    /// What a Tab keypress types, detected from the text's indentation.
+    indent_unit: String,
+    cursor: usize,
+    /// Mistakes still in the text (uncorrected)
+    errors: usize,
+    /// Mistakes that were deleted with backspace, or held at the cursor
+    /// by stop-on-error until the right key landed
+    corrected_errors: usize,
+    /// Every keypress of the session, in order; backspaces aren't logged
+    keystrokes: Vec<Keystroke>,
+    /// Every key from the first keystroke on, backspaces included, enough
+    /// to play the session back
+    inputs: Vec<Input>,
+
+    /// Timestamp of the first keystroke
+    started_at: Option<Duration>,
+    finished_at: Option<Duration>,
+    /// The latest timestamp seen, from a key or a tick
+    now: Duration,
+
+    wpm: f64,
+    raw_wpm: f64,
+    accuracy: f64,
+    samples: Vec<Sample>,
+    consistency: Option<f64>,
+    failed: bool,
+}
+
+impl Session {
+    pub fn new(text: &str, settings: Settings) -> Session {
+        let chars: Vec<char> = text.chars().collect();
+        let len = chars.len();
+        Session {
+            settings,
+            chars,
+            states: vec![CharState::Untyped; len],
+            auto_filled: vec![false; len],
+            held: vec![false; len],
+            indent_unit: detect_indent_unit(text),
+            cursor: 0,
+            errors: 0,
+            corrected_errors: 0,
+            keystrokes: Vec::new(),
+            inputs: Vec::new(),
+            started_at: None,
+            finished_at: None,
+            now: Duration::ZERO,
+            wpm: 0.0,
+            raw_wpm: 0.0,
+            accuracy: 100.0,
+            samples: Vec::new(),
+            consistency: None,
+            failed: false,
+        }
+    }
+
+    /// Adds more text to type after the end, for endless streams
+    pub fn extend(&mut self, text: &str) {
+        self.chars.extend(text.chars());
+        let len = self.chars.len();
+        self.states.resize(len, CharState::Untyped);
+        self.auto_filled.resize(len, false);
+        self.held.resize(len, false);
+    }
+
+    /// Handles one keypress made at `at`. Keys after the session has
+    /// finished (or run out of time) are ignored.
+    pub fn key(&mut self, key: Key, at: Duration) {
+        self.tick(at);
+        if self.is_finished() {
+            return;
+        }
+        match key {
+            Key::Char(c) => self.type_char(c, at),
+            Key::Tab => {
+                let unit = self.indent_unit.clone();
+                for c in unit.chars() {
+                    // Sudden death can end the session partway through the unit
+                    if self.is_finished() {
+                        break;
+                    }
+                    self.type_char(c, at);
+                }
+            }
+            Key::Backspace => self.backspace(),
+            Key::DeleteWord => self.delete_word(),
+        }
+        if let Some(start) = self.started_at {
+            self.inputs.push(Input { at: at.saturating_sub(start), key });
+        }
+    }
+
+    /// Brings the live stats and samples up to `now`, finishing a timed
+    /// session once its time is up
+    pub fn tick(&mut self, now: Duration) {
+        if self.is_finished() {
+            return;
+        }
+        self.now = self.now.max(now);
+        if self.started_at.is_none() {
+            return;
+        }
+        if self.remaining() == Some(Duration::ZERO) {
+            self.finish();
+        } else {
+            self.update_stats();
+            self.take_samples();
+        }
+    }
+
+    fn type_char(&mut self, c: char, at: Duration) {
+        if self.cursor >= self.chars.len() {
+            return;
+        }
+        if self.started_at.is_none() {
+            self.started_at = Some(at);
+        }
+
+        let policy = self.settings.error_policy;
+        let expected = self.chars[self.cursor];
+        let separator = expected == ' ' || expected == '\n';
+        // Without backspace a mistake can't be fixed, so it can't block either
+        if policy == ErrorPolicy::Word && separator && self.settings.allow_backspace && self.word_has_errors() {
+            return;
+        }
+        self.keystrokes.push(Keystroke {
+            at: self.elapsed(),
+            index: self.cursor,
+            expected,
+            typed: c,
+        });
+        let hold = match policy {
+            ErrorPolicy::Letter => true,
+            ErrorPolicy::Word => separator,
+            ErrorPolicy::Off | ErrorPolicy::SuddenDeath => false,
+        };
+        if expected != c && hold {
+            // The cursor stays put; the miss counts as fixed once the right key lands
+            self.states[self.cursor] = CharState::Wrong;
+            self.held[self.cursor] = true;
+            self.update_stats();
+            return;
+        }
+        if expected == c {
+            if self.held[self.cursor] {
+                self.held[self.cursor] = false;
+                self.corrected_errors += 1;
+            }
+            self.states[self.cursor] = CharState::Correct;
+        } else {
+            self.states[self.cursor] = CharState::Wrong;
+            self.errors += 1;
+        }
+        self.cursor += 1;
+        if expected == '\n' && c == '\n' {
+            self.fill_indent();
+        }
+        self.update_stats();
+
+        let word_done = separator || self.cursor == self.chars.len();
+        if policy == ErrorPolicy::SuddenDeath && word_done && self.errors > 0 {
+            self.failed = true;
+            self.finish();
+        } else if self.cursor == self.chars.len() {
+            self.finish();
+        }
+    }
+
+    /// Whether the word before the cursor still has a mistake in it
+    fn word_has_errors(&self) -> bool {
+        let start = self.chars[..self.cursor].iter()
+            .rposition(|c| *c == ' ' || *c == '\n')
+            .map_or(0, |i| i + 1);
+        self.states[start..self.cursor].contains(&CharState::Wrong)
+    }
+
+    /// Auto-fills leading whitespace after a correctly typed newline,
+    /// according to the whitespace policy.
+    fn fill_indent(&mut self) {
+        let target = leading_whitespace(&self.chars[self.cursor..]);
+        let count = match self.settings.whitespace {
+            WhitespacePolicy::Literal => 0,
+            WhitespacePolicy::SkipIndent => target.len(),
+            WhitespacePolicy::AutoIndent => {
+                // Indentation of the line that was just finished
+                let line_start = self.chars[..self.cursor - 1]
+                    .iter()
+                    .rposition(|c| *c == '\n')
+                    .map_or(0, |i| i + 1);
+                let previous = leading_whitespace(&self.chars[line_start..]);
+                target.iter().zip(previous).take_while(|(a, b)| a == b).count()
+            }
+        };
+        for _ in 0..count {
+            self.states[self.cursor] = CharState::Correct;
+            self.auto_filled[self.cursor] = true;
+            self.cursor += 1;
+        }
+    }
+
+    fn backspace(&mut self) {
+        if !self.settings.allow_backspace {
+            return;
+        }
+        // A miss held at the cursor by stop-on-error is deleted along with it
+        if self.cursor < self.chars.len() && self.held[self.cursor] {
+            self.held[self.cursor] = false;
+            self.states[self.cursor] = CharState::Untyped;
+            self.corrected_errors += 1;
+        }
+        // Auto-filled indentation goes away together with the Enter that produced it
+        while self.cursor > 0 && self.auto_filled[self.cursor - 1] {
+            self.cursor -= 1;
+            self.auto_filled[self.cursor] = false;
+            self.states[self.cursor] = CharState::Untyped;
+        }
+        if self.cursor == 0 {
+            return;
+        }
+        self.cursor -= 1;
+        if self.states[self.cursor] == CharState::Wrong {
+            self.errors = self.errors.saturating_sub(1);
+            self.corrected_errors += 1;
+        }
+        self.states[self.cursor] = CharState::Untyped;
+        self.update_stats();
+    }
+
+    /// Deletes back to the start of the previous word (plus any spaces
+    /// after it), one character at a time so states and counts stay right
+    fn delete_word(&mut self) {
+        if !self.settings.allow_backspace {
+            return;
+        }
+        let is_space = |c: char| c == ' ' || c == '\t' || c == '\n';
+        while self.cursor > 0 && is_space(self.chars[self.cursor - 1]) {
+            self.backspace();
+        }
+        while self.cursor > 0 && !is_space(self.chars[self.cursor - 1]) {
+            self.backspace();
+        }
+    }
+
+    fn finish(&mut self) {
+        // A timed session ends exactly at its limit, however late the tick
+        let deadline = self.started_at.zip(self.settings.time_limit).map(|(s, l)| s + l);
+        self.finished_at = Some(deadline.map_or(self.now, |d| d.min(self.now)));
+        self.update_stats();
+        self.take_samples();
+        // The last, partial second
+        let elapsed = self.elapsed();
+        if elapsed > Duration::from_secs(self.samples.len() as u64) {
+            self.push_sample(elapsed, true);
+        }
+        self.consistency = consistency(&self.samples);
+    }
+
+    /// Adds a sample for every whole second that has passed since the last one
+    fn take_samples(&mut self) {
+        let elapsed = self.elapsed();
+        while Duration::from_secs(self.samples.len() as u64 + 1) <= elapsed {
+            self.push_sample(Duration::from_secs(self.samples.len() as u64 + 1), false);
+        }
+    }
+
+    /// Samples the keystrokes since the previous sample up to `end`
+    /// (inclusive for the `last` one, which closes the session)
+    fn push_sample(&mut self, end: Duration, last: bool) {
+        let start = Duration::from_secs(self.samples.len() as u64);
+        let window = self.keystrokes.iter()
+            .filter(|k| k.at >= start && (k.at < end || last && k.at == end));
+        let (typed, errors) = window.fold((0, 0), |(n, e), k| (n + 1, e + usize::from(k.typed != k.expected)));
+        let minutes = (end - start).as_secs_f64() / 60.0;
+        self.samples.push(Sample {
+            time: end.as_secs_f64(),
+            wpm: self.wpm,
+            raw_wpm: if minutes > 0.0 { typed as f64 / 5.0 / minutes } else { 0.0 },
+            errors,
+        });
+    }
+
+    fn update_stats(&mut self) {
+        if self.started_at.is_some() {
+            let elapsed = self.elapsed().as_secs_f64();
+            if elapsed > 0.0 {
+                // WPM: chars typed / 5 (standard word length) / minutes
+                let correct_chars = self.states.iter()
+                    .zip(&self.auto_filled)
+                    .filter(|(s, auto)| **s == CharState::Correct && !**auto)
+                    .count() as f64;
+                self.wpm = (correct_chars / 5.0) / (elapsed / 60.0);
+                self.raw_wpm = (self.keystrokes.len() as f64 / 5.0) / (elapsed / 60.0);
+            }
+            let typed = self.keystrokes.len();
+            if typed > 0 {
+                let missed = self.keystrokes.iter().filter(|k| k.typed != k.expected).count();
+                self.accuracy = ((typed - missed) as f64 / typed as f64) * 100.0;
+            }
+        }
+    }
+
+    pub fn settings(&self) -> Settings {
+        self.settings
+    }
+
+    pub fn chars(&self) -> &[char] {
+        &self.chars
+    }
+
+    pub fn states(&self) -> &[CharState] {
+        &self.states
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Characters left between the cursor and the end of the text
+    pub fn untyped(&self) -> usize {
+        self.chars.len() - self.cursor
+    }
+
+    /// Mistakes still in the text (uncorrected)
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+
+    /// Mistakes that were deleted with backspace, or fixed in place
+    /// under a stop-on-error policy
+    pub fn corrected_errors(&self) -> usize {
+        self.corrected_errors
+    }
+
+    /// Every keypress of the session, in order; backspaces aren't logged
+    pub fn keystrokes(&self) -> &[Keystroke] {
+        &self.keystrokes
+    }
+
+    /// Every key from the first keystroke on, for [`Replay`]
+    pub fn inputs(&self) -> &[Input] {
+        &self.inputs
+    }
+
+    /// Net WPM: correct characters left in the text
+    pub fn wpm(&self) -> f64 {
+        self.wpm
+    }
+
+    /// Raw WPM: every keystroke, including mistakes and deleted ones
+    pub fn raw_wpm(&self) -> f64 {
+        self.raw_wpm
+    }
+
+    /// Share of keystrokes that were right when typed; corrections don't undo a miss
+    pub fn accuracy(&self) -> f64 {
+        self.accuracy
+    }
+
+    /// One sample per elapsed second, plus a partial one at the finish
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// Rhythm score of the finished session; `None` until there's enough to measure
+    pub fn consistency(&self) -> Option<f64> {
+        self.consistency
+    }
+
+    /// Sudden death ended the session early
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished_at.is_some()
+    }
+
+    /// Time since the first keystroke, up to the latest key or tick
+    pub fn elapsed(&self) -> Duration {
+        match self.started_at {
+            Some(start) => self.finished_at.unwrap_or(self.now).saturating_sub(start),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Time left in a timed session; `None` without a time limit
+    pub fn remaining(&self) -> Option<Duration> {
+        self.settings.time_limit.map(|limit| limit.saturating_sub(self.elapsed()))
+    }
+
+    /// How far through the session is, from 0 to 1: by time with a time
+    /// limit, otherwise by text
+    pub fn progress(&self) -> f64 {
+        if let Some(limit) = self.settings.time_limit {
+            return (self.elapsed().as_secs_f64() / limit.as_secs_f64()).min(1.0);
+        }
+        if self.chars.is_empty() { return 0.0; }
+        self.cursor as f64 / self.chars.len() as f64
+    }
+}
+
+/// Plays a recorded session back: the recorded inputs are fed into a
+/// fresh session over the same text as playback time passes, so every
+/// intermediate state is exactly what the typist saw.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    session: Session,
+    inputs: Vec<Input>,
+    /// Index of the next input to apply
+    next: usize,
+}
+
+impl Replay {
+    pub fn new(text: &str, settings: Settings, inputs: Vec<Input>) -> Replay {
+        Replay { session: Session::new(text, settings), inputs, next: 0 }
+    }
+
+    /// Applies every input up to `at`, the playback time since the first
+    /// keystroke. Time only moves forward; to go back, start a new replay.
+    pub fn advance_to(&mut self, at: Duration) {
+        while let Some(input) = self.inputs.get(self.next).filter(|i| i.at <= at) {
+            self.session.key(input.key, input.at);
+            self.next += 1;
+        }
+        if self.next > 0 {
+            self.session.tick(at);
+        }
+    }
+
+    /// The session as it stood at the latest playback time
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Playback time of the last input, or the time limit if that's later
+    pub fn duration(&self) -> Duration {
+        let last = self.inputs.last().map_or(Duration::ZERO, |i| i.at);
+        self.session.settings().time_limit.map_or(last, |limit| limit.max(last))
+    }
+
+    /// Whether playback has reached the end of the recording
+    pub fn is_done(&self) -> bool {
+        let untimed = self.session.settings().time_limit.is_none();
+        self.session.is_finished() || untimed && self.next == self.inputs.len()
+    }
+}
+
+/// How many characters a typist at a steady `wpm` has typed `elapsed`
+/// after their first keystroke
+pub fn pace_chars(wpm: f64, elapsed: Duration) -> usize {
+    (wpm * 5.0 * elapsed.as_secs_f64() / 60.0) as usize
+}
+
+/// 100 minus the coefficient of variation (as a percentage) of raw WPM
+/// across the session's whole seconds: 100 is a perfectly even rhythm,
+/// and the score bottoms out at 0. Needs at least two full seconds.
+fn consistency(samples: &[Sample]) -> Option<f64> {
+    let speeds: Vec<f64> = samples.iter()
+        .filter(|s| s.time.fract() == 0.0)
+        .map(|s| s.raw_wpm)
+        .collect();
+    if speeds.len() < 2 {
+        return None;
+    }
+    let n = speeds.len() as f64;
+    let mean = speeds.iter().sum::<f64>() / n;
+    if mean == 0.0 {
+        return None;
+    }
+    let variance = speeds.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    Some((100.0 - variance.sqrt() / mean * 100.0).max(0.0))
+}
+
+fn leading_whitespace(chars: &[char]) -> &[char] {
+    let len = chars.iter().take_while(|c| **c == ' ' || **c == '\t').count();
+    &chars[..len]
+}
+
+/// Guesses the indentation unit of a text: a tab if any line is
+/// tab-indented, otherwise the smallest run of leading spaces (default 4).
+fn detect_indent_unit(text: &str) -> String {
+    if text.lines().any(|l| l.starts_with('\t')) {
+        return "\t".to_string();
+    }
+    let width = text.lines()
+        .map(|l| l.len() - l.trim_start_matches(' ').len())
+        .filter(|n| *n > 0)
+        .min()
+        .unwrap_or(4);
+    " ".repeat(width)
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use rhonetyping::engine::Replay;
+
+/// Whether sessions race a ghost. Racing a fixed speed is the pace caret's
+/// job (`pace_wpm`).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GhostMode {
+    Off,
+    /// The best recorded run on the same snippet (snippet mode only)
+    Best,
+}
+
+impl GhostMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            GhostMode::Off => "off",
+            GhostMode::Best => "personal best",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            GhostMode::Off => GhostMode::Best,
+            GhostMode::Best => GhostMode::Off,
+        }
+    }
+}
+
+/// A second cursor replaying an earlier run alongside the live session.
+/// It starts with the first keystroke and moves on the session's own clock.
+#[derive(Debug, Clone)]
+pub struct Ghost {
+    replay: Replay,
+}
+
+impl Ghost {
+    pub fn new(replay: Replay) -> Ghost {
+        Ghost { replay }
+    }
+
+    /// Moves the ghost to `elapsed` after the session's first keystroke
+    pub fn advance_to(&mut self, elapsed: Duration) {
+        self.replay.advance_to(elapsed);
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.replay.session().cursor()
+    }
+}
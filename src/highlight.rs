@@ -0,0 +1,111 @@
+use crate::snippets::Language;
+
+/// What a character of code is part of, for colouring untyped text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Token {
+    Plain,
+    Keyword,
+    String,
+    Number,
+    Comment,
+    Punctuation,
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+    "true", "type", "unsafe", "use", "where", "while",
+];
+
+const PYTHON_KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+    "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global",
+    "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return",
+    "self", "try", "while", "with", "yield",
+];
+
+/// Classifies every character of `chars`, one token per character.
+/// Prose comes back all `Plain`.
+pub fn tokenize(lang: Language, chars: &[char]) -> Vec<Token> {
+    let (keywords, comment): (&[&str], &[char]) = match lang {
+        Language::English => return vec![Token::Plain; chars.len()],
+        Language::Rust => (RUST_KEYWORDS, &['/', '/']),
+        Language::Python => (PYTHON_KEYWORDS, &['#']),
+    };
+
+    let mut tokens = vec![Token::Plain; chars.len()];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let token = if chars[i..].starts_with(comment) {
+            i = line_end(chars, i);
+            Token::Comment
+        } else if c == '"' || (c == '\'' && (lang == Language::Python || is_char_literal(&chars[i..]))) {
+            i = string_end(chars, i, lang);
+            Token::String
+        } else if c.is_ascii_digit() {
+            i = word_end(chars, i);
+            Token::Number
+        } else if c.is_alphanumeric() || c == '_' {
+            i = word_end(chars, i);
+            let word: String = chars[start..i].iter().collect();
+            if keywords.contains(&word.as_str()) { Token::Keyword } else { Token::Plain }
+        } else if c.is_ascii_punctuation() {
+            i += 1;
+            Token::Punctuation
+        } else {
+            i += 1;
+            Token::Plain
+        };
+        tokens[start..i].fill(token);
+    }
+    tokens
+}
+
+fn line_end(chars: &[char], from: usize) -> usize {
+    chars[from..].iter().position(|c| *c == '\n').map_or(chars.len(), |n| from + n)
+}
+
+/// End of an identifier or number; a number may contain a decimal point,
+/// but not a range like `0..n`
+fn word_end(chars: &[char], from: usize) -> usize {
+    let numeric = chars[from].is_ascii_digit();
+    let mut i = from;
+    while i < chars.len() {
+        let c = chars[i];
+        let decimal = numeric && c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit);
+        if !(c.is_alphanumeric() || c == '_' || decimal) {
+            break;
+        }
+        i += 1;
+    }
+    i
+}
+
+/// `'a'` or `'\n'` rather than a lifetime like `'a`
+fn is_char_literal(rest: &[char]) -> bool {
+    matches!(rest, ['\'', '\\', ..] | ['\'', _, '\'', ..])
+}
+
+/// End (exclusive) of the string literal opening at `from`, honouring
+/// escapes and Python's triple quotes. Unterminated strings end at the
+/// line break (or the text, for triple quotes).
+fn string_end(chars: &[char], from: usize, lang: Language) -> usize {
+    let quote = chars[from];
+    let triple = lang == Language::Python && chars[from..].starts_with(&[quote; 3]);
+    let mut i = from + if triple { 3 } else { 1 };
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            c if c == quote && (!triple || chars[i..].starts_with(&[quote; 3])) => {
+                return (i + if triple { 3 } else { 1 }).min(chars.len());
+            }
+            // Rust strings may span lines; Python's single-quoted ones can't
+            '\n' if lang == Language::Python && !triple => return i,
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+use crate::recording::{RECORDINGS_DIR, Recording, Recordings};
+use crate::snippets::Language;
+
+const HISTORY_FILE: &str = "history.jsonl";
+/// How many recent sessions the rolling average on the results screen covers
+pub const ROLLING_WINDOW: usize = 10;
+
+/// One finished session, stored as a single JSON line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    /// Unix timestamp (seconds) of when the session finished
+    pub timestamp: u64,
+    pub language: Language,
+    pub snippet_id: String,
+    /// Net WPM
+    pub wpm: f64,
+    /// Raw WPM (absent from records written before it was tracked)
+    #[serde(default)]
+    pub raw_wpm: f64,
+    pub accuracy: f64,
+    /// Uncorrected errors
+    pub errors: usize,
+    #[serde(default)]
+    pub corrected_errors: usize,
+    /// 0–100 rhythm score; `None` for very short or older sessions
+    #[serde(default)]
+    pub consistency: Option<f64>,
+    /// Seconds from first keystroke to finish
+    pub elapsed: f64,
+    pub chars: usize,
+    /// Id of the session's keystroke recording, if one was saved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recording: Option<u64>,
+}
+
+impl SessionRecord {
+    /// Which sessions this one is comparable with: timed runs of the same
+    /// length, word runs of the same count and list, drills, or snippets
+    pub fn kind(&self) -> &str {
+        match self.snippet_id.split_once(':') {
+            Some(("time" | "words", _)) => &self.snippet_id,
+            _ if self.snippet_id == "drill" => "drill",
+            _ => "snippet",
+        }
+    }
+}
+
+/// Every past session, oldest first, backed by an append-only file in the
+/// XDG data directory
+#[derive(Debug, Default)]
+pub struct History {
+    pub records: Vec<SessionRecord>,
+    /// Keystroke recordings of the sessions, kept beside the history file
+    pub recordings: Recordings,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// Loads the history file. A missing file is an empty history; lines
+    /// that fail to parse are skipped so one bad write can't lose the rest.
+    pub fn load() -> History {
+        History::open(paths::data_dir())
+    }
+
+    /// Loads the history kept in `dir`; `None` keeps it in memory only
+    pub fn open(dir: Option<PathBuf>) -> History {
+        let path = dir.as_ref().map(|d| d.join(HISTORY_FILE));
+        let records = path.as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|text| {
+                text.lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let recordings = Recordings::open(dir.map(|d| d.join(RECORDINGS_DIR)));
+        History { records, recordings, path }
+    }
+
+    /// Records a session in memory and appends it to the history file
+    pub fn append(&mut self, record: SessionRecord) -> io::Result<()> {
+        let line = serde_json::to_string(&record).map_err(io::Error::other)?;
+        self.records.push(record);
+
+        let path = self.path.as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory (HOME unset)"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", line)
+    }
+
+    /// Saves a session's recording, then deletes the oldest ones beyond the
+    /// limit except each snippet's fastest, which its ghost replays
+    pub fn save_recording(&mut self, recording: &Recording) -> io::Result<()> {
+        self.recordings.save(recording)?;
+        let mut best: BTreeMap<&str, &SessionRecord> = BTreeMap::new();
+        for record in self.records.iter().filter(|r| r.recording.is_some()) {
+            let entry = best.entry(&record.snippet_id).or_insert(record);
+            if record.wpm > entry.wpm {
+                *entry = record;
+            }
+        }
+        let keep = best.values().filter_map(|r| r.recording).collect();
+        self.recordings.prune(&keep)
+    }
+
+    /// The id of `record`'s recording, if it is still on disk
+    pub fn recording_of(&self, record: &SessionRecord) -> Option<u64> {
+        record.recording.filter(|id| self.recordings.has(*id))
+    }
+
+    /// Compares a just-finished session against the sessions of the same
+    /// language and kind recorded before it. Call before appending `record`.
+    pub fn compare(&self, record: &SessionRecord) -> Comparison {
+        let same_kind: Vec<&SessionRecord> = self.records.iter()
+            .filter(|r| r.language == record.language && r.kind() == record.kind())
+            .collect();
+        let same_snippet = same_kind.iter().filter(|r| r.snippet_id == record.snippet_id);
+        let recent = &same_kind[same_kind.len().saturating_sub(ROLLING_WINDOW)..];
+
+        Comparison {
+            best_wpm: max(same_kind.iter().map(|r| r.wpm)),
+            best_accuracy: max(same_kind.iter().map(|r| r.accuracy)),
+            snippet_best_wpm: max(same_snippet.clone().map(|r| r.wpm)),
+            snippet_best_accuracy: max(same_snippet.map(|r| r.accuracy)),
+            avg_wpm: mean(recent.iter().map(|r| r.wpm)),
+            avg_accuracy: mean(recent.iter().map(|r| r.accuracy)),
+        }
+    }
+
+    /// Records matching `filter` (all languages when `None`), in `sort` order
+    pub fn view(&self, sort: HistorySort, filter: Option<Language>) -> Vec<&SessionRecord> {
+        let mut rows: Vec<&SessionRecord> = self.records.iter()
+            .filter(|r| filter.is_none_or(|lang| r.language == lang))
+            .collect();
+        match sort {
+            HistorySort::Newest => rows.sort_by_key(|r| std::cmp::Reverse(r.timestamp)),
+            HistorySort::Wpm => rows.sort_by(|a, b| b.wpm.total_cmp(&a.wpm)),
+            HistorySort::Accuracy => rows.sort_by(|a, b| b.accuracy.total_cmp(&a.accuracy)),
+        }
+        rows
+    }
+}
+
+/// How a session stacks up against earlier ones of the same language and kind.
+/// Every field is `None` when there is nothing to compare against yet.
+#[derive(Debug, Clone, Default)]
+pub struct Comparison {
+    pub best_wpm: Option<f64>,
+    pub best_accuracy: Option<f64>,
+    /// Bests on this exact snippet
+    pub snippet_best_wpm: Option<f64>,
+    pub snippet_best_accuracy: Option<f64>,
+    /// Mean over the last `ROLLING_WINDOW` sessions
+    pub avg_wpm: Option<f64>,
+    pub avg_accuracy: Option<f64>,
+}
+
+/// Whether `value` beats a previous best (a first attempt isn't a record)
+pub fn is_record(value: f64, best: Option<f64>) -> bool {
+    best.is_some_and(|best| value > best)
+}
+
+fn max(values: impl Iterator<Item = f64>) -> Option<f64> {
+    values.reduce(f64::max)
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, n), v| (sum + v, n + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+/// Ordering of the History screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistorySort {
+    Newest,
+    Wpm,
+    Accuracy,
+}
+
+impl HistorySort {
+    pub fn label(&self) -> &'static str {
+        match self {
+            HistorySort::Newest => "newest",
+            HistorySort::Wpm => "wpm",
+            HistorySort::Accuracy => "accuracy",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            HistorySort::Newest => HistorySort::Wpm,
+            HistorySort::Wpm => HistorySort::Accuracy,
+            HistorySort::Accuracy => HistorySort::Newest,
+        }
+    }
+}
+
+pub fn now_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Formats a Unix timestamp as `YYYY-MM-DD HH:MM` (UTC)
+pub fn format_timestamp(ts: u64) -> String {
+    let days = (ts / 86_400) as i64;
+    let secs = ts % 86_400;
+
+    // Civil-from-days (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, secs / 3600, secs % 3600 / 60)
+}
@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use rhonetyping::engine::Keystroke;
+
+use crate::paths;
+
+const KEYSTATS_FILE: &str = "keystats.json";
+/// Gaps longer than this are pauses, not typing, and don't count as latency
+const MAX_LATENCY: Duration = Duration::from_secs(2);
+/// Keys and bigrams seen fewer times than this are left off the Stats screen
+pub const MIN_SAMPLES: u32 = 5;
+
+/// Running totals for one key or bigram
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeyStat {
+    pub hits: u32,
+    pub misses: u32,
+    /// Sum of latencies in milliseconds, over `latency_samples` keypresses
+    pub latency_ms: f64,
+    pub latency_samples: u32,
+}
+
+impl KeyStat {
+    pub fn count(&self) -> u32 {
+        self.hits + self.misses
+    }
+
+    /// Percentage of attempts typed wrong
+    pub fn error_rate(&self) -> f64 {
+        if self.count() == 0 { return 0.0; }
+        self.misses as f64 / self.count() as f64 * 100.0
+    }
+
+    /// Mean time from the previous keypress, in milliseconds
+    pub fn mean_latency(&self) -> Option<f64> {
+        (self.latency_samples > 0).then(|| self.latency_ms / self.latency_samples as f64)
+    }
+
+    fn add(&mut self, correct: bool, latency: Option<Duration>) {
+        if correct {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        if let Some(latency) = latency {
+            self.latency_ms += latency.as_secs_f64() * 1000.0;
+            self.latency_samples += 1;
+        }
+    }
+}
+
+/// Per-key and per-bigram accuracy and latency over every session, keyed by
+/// the expected character(s), stored as one JSON file in the data directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct KeyStats {
+    pub keys: BTreeMap<String, KeyStat>,
+    pub bigrams: BTreeMap<String, KeyStat>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl KeyStats {
+    /// Loads the stats file; a missing or unreadable file starts from zero
+    pub fn load() -> KeyStats {
+        KeyStats::open(paths::data_dir())
+    }
+
+    /// Loads the stats kept in `dir`; `None` keeps them in memory only
+    pub fn open(dir: Option<PathBuf>) -> KeyStats {
+        let path = dir.map(|d| d.join(KEYSTATS_FILE));
+        let mut stats: KeyStats = path.as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        stats.path = path;
+        stats
+    }
+
+    /// Folds a session's keystroke log into the totals. A keystroke's
+    /// latency and bigram are only counted when it directly follows the
+    /// previous one in the text, so corrections don't skew either.
+    /// Characters typed along with the previous one by the same keypress,
+    /// like the rest of a Tab's indent unit, aren't counted at all.
+    pub fn record(&mut self, log: &[Keystroke]) {
+        let mut prev: Option<&Keystroke> = None;
+        for key in log {
+            if prev.is_some_and(|p| p.at == key.at && p.index + 1 == key.index) {
+                prev = Some(key);
+                continue;
+            }
+            let correct = key.typed == key.expected;
+            let follows = prev.filter(|p| p.index + 1 == key.index);
+            let latency = follows
+                .map(|p| key.at.saturating_sub(p.at))
+                .filter(|l| *l <= MAX_LATENCY);
+
+            self.keys.entry(key.expected.to_string()).or_default().add(correct, latency);
+            if let Some(p) = follows {
+                let bigram: String = [p.expected, key.expected].iter().collect();
+                self.bigrams.entry(bigram).or_default().add(correct, latency);
+            }
+            prev = Some(key);
+        }
+    }
+
+    /// Writes the totals back to the stats file
+    pub fn save(&self) -> io::Result<()> {
+        let path = self.path.as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory (HOME unset)"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+
+    /// Keys (or bigrams, when `bigrams` is set) with at least `MIN_SAMPLES`
+    /// attempts, worst first by `sort`
+    pub fn ranked(&self, bigrams: bool, sort: KeySort) -> Vec<(&str, &KeyStat)> {
+        let map = if bigrams { &self.bigrams } else { &self.keys };
+        let mut rows: Vec<(&str, &KeyStat)> = map.iter()
+            .filter(|(_, s)| s.count() >= MIN_SAMPLES)
+            .map(|(k, s)| (k.as_str(), s))
+            .collect();
+        let latency = |s: &KeyStat| s.mean_latency().unwrap_or(0.0);
+        match sort {
+            KeySort::Errors => rows.sort_by(|a, b| {
+                b.1.error_rate().total_cmp(&a.1.error_rate())
+                    .then(latency(b.1).total_cmp(&latency(a.1)))
+            }),
+            KeySort::Latency => rows.sort_by(|a, b| latency(b.1).total_cmp(&latency(a.1))),
+        }
+        rows
+    }
+}
+
+/// Ordering of the Stats screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeySort {
+    Errors,
+    Latency,
+}
+
+impl KeySort {
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeySort::Errors => "errors",
+            KeySort::Latency => "latency",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            KeySort::Errors => KeySort::Latency,
+            KeySort::Latency => KeySort::Errors,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rhonetyping::engine::{Key, Session, Settings};
+
+    #[test]
+    fn tab_counts_as_one_keypress() {
+        let mut session = Session::new("a\n    b", Settings::default());
+        session.key(Key::Char('a'), Duration::ZERO);
+        session.key(Key::Char('\n'), Duration::from_millis(200));
+        session.key(Key::Tab, Duration::from_millis(500));
+        session.key(Key::Char('b'), Duration::from_millis(600));
+
+        let mut stats = KeyStats::default();
+        stats.record(session.keystrokes());
+        let space = &stats.keys[" "];
+        assert_eq!(space.count(), 1);
+        assert_eq!(space.mean_latency(), Some(300.0));
+        assert_eq!(stats.keys["b"].mean_latency(), Some(100.0));
+    }
+}
@@ -0,0 +1,5 @@
+//! The headless typing engine behind rhonetyping, for embedding in other
+//! frontends. The terminal app in `main.rs` is one such frontend.
+
+pub mod clock;
+pub mod engine;
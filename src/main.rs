@@ -1,26 +1,104 @@
+mod paths;
+mod cli;
+mod config;
 mod snippets;
+mod source;
+mod history;
+mod recording;
+mod race;
+mod keystats;
+mod drill;
+mod ghost;
+mod highlight;
+mod theme;
+mod words;
 mod app;
 mod ui;
 
 use std::io;
 use std::panic;
+use std::process;
 use std::time::Duration;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyModifiers},
+    cursor::SetCursorStyle,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
 
+use rhonetyping::engine::Key;
+
 use app::{App, Screen};
+use cli::Command;
+use config::Config;
+use history::History;
+use race::Race;
+use keystats::KeyStats;
+use snippets::Library;
+use source::SourceSnippets;
+use theme::Theme;
+use words::Words;
 use ui::draw;
 
 fn restore_terminal() {
     let _ = disable_raw_mode();
-    let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, SetCursorStyle::DefaultUserShape);
 }
 
 fn main() -> io::Result<()> {
+    let (options, race) = match cli::parse_args() {
+        Command::Run(options) => (options, None),
+        Command::Host(options) => {
+            let name = options.name.clone().unwrap_or_else(race::default_name);
+            let port = options.port.unwrap_or(race::DEFAULT_PORT);
+            match Race::host(port, name) {
+                Ok(race) => (options, Some(race)),
+                Err(e) => {
+                    eprintln!("could not host on port {}: {}", port, e);
+                    process::exit(1);
+                }
+            }
+        }
+        Command::Join(address, options) => {
+            let name = options.name.clone().unwrap_or_else(race::default_name);
+            match Race::join(&address, name) {
+                Ok(race) => (options, Some(race)),
+                Err(e) => {
+                    eprintln!("could not join {}: {}", address, e);
+                    process::exit(1);
+                }
+            }
+        }
+        Command::Stats => {
+            cli::print_stats(&History::load(), &KeyStats::load());
+            return Ok(());
+        }
+        Command::Export(format) => {
+            cli::export(&History::load(), format);
+            return Ok(());
+        }
+    };
+    let source_paths = &options.sources;
+    let (sources, source_warnings) = match SourceSnippets::load(source_paths) {
+        Ok(sources) => sources,
+        Err(e) => {
+            eprintln!("could not read snippet source: {}", e);
+            process::exit(1);
+        }
+    };
+    if !source_paths.is_empty() && sources.is_empty() {
+        eprintln!("no usable snippets found in the given sources");
+        process::exit(1);
+    }
+
+    let (library, mut warnings) = Library::load();
+    warnings.extend(source_warnings);
+    let (themes, theme_warnings) = Theme::load_all();
+    warnings.extend(theme_warnings);
+    let (config, config_warning) = Config::load();
+    warnings.extend(config_warning);
+
     // Restore terminal even on panic
     panic::set_hook(Box::new(|info| {
         restore_terminal();
@@ -31,16 +109,28 @@ fn main() -> io::Result<()> {
     let mut stdout = io::stdout();
     // Note: no EnableMouseCapture — we don't need it and it causes
     // garbage characters to appear when hovering after exit
-    execute!(stdout, EnterAlternateScreen)?;
+    // The terminal cursor only shows for the bar cursor style
+    execute!(stdout, EnterAlternateScreen, SetCursorStyle::BlinkingBar)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new();
+    let mut app = App::new(library, sources, Words::load(), History::load(), KeyStats::load(), themes, config);
+    match race {
+        Some(race) if race.is_host() => app.host_race(race, options.lang),
+        Some(race) => app.join_race(race),
+        None if options.starts_session() => app.start_with(options.lang, options.mode),
+        None => {}
+    }
     let result = run(&mut terminal, &mut app);
 
     restore_terminal();
     terminal.show_cursor()?;
 
+    // Reported after the alternate screen is gone so they stay visible
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
+    }
+
     result
 }
 
@@ -48,54 +138,163 @@ fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) ->
     loop {
         terminal.draw(|f| draw(f, app))?;
 
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                // Global quit
-                if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
-                    return Ok(());
-                }
+        if event::poll(Duration::from_millis(100))?
+            && let Event::Key(key) = event::read()?
+        {
+            // Global quit
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                return Ok(());
+            }
 
-                match app.screen {
-                    Screen::Menu => handle_menu(app, key.code),
-                    Screen::Typing => handle_typing(app, key.code),
-                    Screen::Results => handle_results(app, key.code),
-                }
-                if app.should_quit {
-                    return Ok(());
-                }
+            match app.screen {
+                Screen::Menu => handle_menu(app, key.code),
+                Screen::Typing => handle_typing(app, key),
+                Screen::Results => handle_results(app, key.code),
+                Screen::History => handle_history(app, key.code),
+                Screen::Stats => handle_stats(app, key.code),
+                Screen::Themes => handle_themes(app, key.code),
+                Screen::Settings => handle_settings(app, key.code),
+                Screen::Replay => handle_replay(app, key.code),
+                Screen::Lobby => handle_lobby(app, key.code),
+            }
+            if app.should_quit {
+                return Ok(());
             }
         }
 
-        // Tick for WPM updates while typing
-        if app.screen == Screen::Typing {
+        // Tick for WPM updates while typing, to move a replay along, and to
+        // keep up with a race on any screen
+        if matches!(app.screen, Screen::Typing | Screen::Replay) || app.race.is_some() {
             app.tick();
         }
     }
 }
 
 fn handle_menu(app: &mut App, key: KeyCode) {
+    let keys = app.config.keys;
     match key {
         KeyCode::Up | KeyCode::Char('k') => app.menu_prev(),
         KeyCode::Down | KeyCode::Char('j') => app.menu_next(),
         KeyCode::Enter | KeyCode::Char(' ') => app.start_session(),
-        KeyCode::Char('q') => app.quit(),
+        KeyCode::Char(c) if c == keys.mode => app.cycle_mode(),
+        KeyCode::Char(c) if c == keys.whitespace => app.cycle_whitespace(),
+        KeyCode::Char(c) if c == keys.errors => app.cycle_error_policy(),
+        KeyCode::Char(c) if c == keys.word_list => app.cycle_word_list(),
+        KeyCode::Char(c) if c == keys.punctuation => app.toggle_punctuation(),
+        KeyCode::Char(c) if c == keys.numbers => app.toggle_numbers(),
+        KeyCode::Char(c) if c == keys.history => app.open_history(),
+        KeyCode::Char(c) if c == keys.stats => app.open_stats(),
+        KeyCode::Char(c) if c == keys.theme => app.open_themes(),
+        KeyCode::Char(c) if c == keys.settings => app.open_settings(),
+        KeyCode::Char(c) if c == keys.quit => app.quit(),
         _ => {}
     }
 }
 
-fn handle_typing(app: &mut App, key: KeyCode) {
-    match key {
+fn handle_typing(app: &mut App, key: KeyEvent) {
+    // Terminals report Ctrl+Backspace as Backspace with a modifier or as Ctrl+H
+    let word = key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+    match key.code {
+        KeyCode::Backspace if word => app.key(Key::DeleteWord),
+        KeyCode::Char('w' | 'h') if key.modifiers.contains(KeyModifiers::CONTROL) => app.key(Key::DeleteWord),
         KeyCode::Esc => app.go_to_menu(),
-        KeyCode::Backspace => app.backspace(),
-        KeyCode::Char(c) => app.type_char(c),
+        KeyCode::Backspace => app.key(Key::Backspace),
+        KeyCode::Enter => app.key(Key::Char('\n')),
+        KeyCode::Tab => app.key(Key::Tab),
+        KeyCode::Char(c) => app.key(Key::Char(c)),
         _ => {}
     }
 }
 
 fn handle_results(app: &mut App, key: KeyCode) {
+    let keys = app.config.keys;
+    // In a race the next snippet is the host's call
+    if app.race.is_some() {
+        match key {
+            KeyCode::Enter => app.open_lobby(),
+            KeyCode::Esc => app.go_to_menu(),
+            KeyCode::Char(c) if c == keys.retry || c == keys.new_snippet => app.open_lobby(),
+            KeyCode::Char(c) if c == keys.replay => app.replay_last(),
+            KeyCode::Char(c) if c == keys.quit => app.go_to_menu(),
+            _ => {}
+        }
+        return;
+    }
+    match key {
+        KeyCode::Enter => app.restart_session(),
+        KeyCode::Esc => app.go_to_menu(),
+        KeyCode::Char(c) if c == keys.retry => app.restart_session(),
+        KeyCode::Char(c) if c == keys.new_snippet => app.new_snippet(),
+        KeyCode::Char(c) if c == keys.replay => app.replay_last(),
+        KeyCode::Char(c) if c == keys.quit => app.go_to_menu(),
+        _ => {}
+    }
+}
+
+fn handle_history(app: &mut App, key: KeyCode) {
+    match key {
+        KeyCode::Up | KeyCode::Char('k') => app.history_scroll_up(),
+        KeyCode::Down | KeyCode::Char('j') => app.history_scroll_down(),
+        KeyCode::Char('s') => app.history_next_sort(),
+        KeyCode::Char('l') => app.history_next_filter(),
+        KeyCode::Enter => app.history_replay(),
+        KeyCode::Esc | KeyCode::Char('q') => app.go_to_menu(),
+        _ => {}
+    }
+}
+
+fn handle_stats(app: &mut App, key: KeyCode) {
+    match key {
+        KeyCode::Char('s') => app.stats_next_sort(),
+        KeyCode::Esc | KeyCode::Char('q') => app.go_to_menu(),
+        _ => {}
+    }
+}
+
+fn handle_themes(app: &mut App, key: KeyCode) {
+    match key {
+        KeyCode::Up | KeyCode::Char('k') => app.theme_prev(),
+        KeyCode::Down | KeyCode::Char('j') => app.theme_next(),
+        KeyCode::Enter | KeyCode::Char(' ') => app.theme_confirm(),
+        KeyCode::Esc | KeyCode::Char('q') => app.theme_cancel(),
+        _ => {}
+    }
+}
+
+fn handle_settings(app: &mut App, key: KeyCode) {
+    if app.settings_capture {
+        match key {
+            KeyCode::Char(c) => app.settings_bind(c),
+            KeyCode::Esc => app.settings_cancel_capture(),
+            _ => {}
+        }
+        return;
+    }
+    match key {
+        KeyCode::Up | KeyCode::Char('k') => app.settings_prev(),
+        KeyCode::Down | KeyCode::Char('j') => app.settings_next(),
+        KeyCode::Left | KeyCode::Char('h') => app.settings_change(false),
+        KeyCode::Right | KeyCode::Char('l') | KeyCode::Enter | KeyCode::Char(' ') => app.settings_change(true),
+        KeyCode::Esc | KeyCode::Char('q') => app.go_to_menu(),
+        _ => {}
+    }
+}
+
+fn handle_replay(app: &mut App, key: KeyCode) {
+    match key {
+        KeyCode::Char(' ') => app.replay_toggle_pause(),
+        KeyCode::Right | KeyCode::Char('l') => app.replay_faster(),
+        KeyCode::Left | KeyCode::Char('h') => app.replay_slower(),
+        KeyCode::Char('r') => app.replay_restart(),
+        KeyCode::Esc | KeyCode::Char('q') => app.close_replay(),
+        _ => {}
+    }
+}
+
+fn handle_lobby(app: &mut App, key: KeyCode) {
     match key {
-        KeyCode::Enter | KeyCode::Char('r') => app.restart_session(),
-        KeyCode::Char('n') => app.new_snippet(),
+        KeyCode::Enter | KeyCode::Char(' ') => app.race_start(),
+        KeyCode::Char(c) if c == app.config.keys.new_snippet => app.race_new_snippet(),
         KeyCode::Esc | KeyCode::Char('q') => app.go_to_menu(),
         _ => {}
     }
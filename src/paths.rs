@@ -0,0 +1,22 @@
+use std::env;
+use std::path::PathBuf;
+
+const APP_DIR: &str = "rhonetyping";
+
+/// `$XDG_CONFIG_HOME/rhonetyping`, falling back to `~/.config/rhonetyping`
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// `$XDG_DATA_HOME/rhonetyping`, falling back to `~/.local/share/rhonetyping`
+pub fn data_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    let base = env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))?;
+    Some(base.join(APP_DIR))
+}
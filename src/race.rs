@@ -0,0 +1,551 @@
+//! Racing other instances over the local network. One instance hosts:
+//! it picks the snippet, says when to start and keeps the standings.
+//! The others join it and report their progress as they type.
+//!
+//! The protocol is one JSON object per line over plain TCP:
+//!
+//! ```text
+//! → {"type":"join","name":"ana"}
+//! ← {"type":"welcome","id":2}
+//! ← {"type":"snippet","id":"Built-in/rust/fibonacci","snippet":{...}}
+//! ← {"type":"start","settings":{"whitespace":"literal","error_policy":"off",...}}
+//! → {"type":"progress","progress":0.25,"wpm":71.0,"finished":false,"out":false}
+//! ← {"type":"standings","racers":[{"id":0,"name":"bo","progress":0.3,"wpm":64.2,"place":null,"out":false}, ...]}
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use rhonetyping::engine::Settings;
+
+use crate::snippets::Snippet;
+
+/// The port `host` listens on and `join` connects to unless told otherwise
+pub const DEFAULT_PORT: u16 = 7340;
+/// The host's own racer id; joiners count up from 1
+const HOST_ID: usize = 0;
+/// How long joining waits for the host to answer
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// A peer that takes longer than this to accept a line is hung up on
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest line a peer may send, well past any snippet; a peer that sends
+/// a longer one is hung up on
+const MAX_LINE: usize = 1024 * 1024;
+
+/// What others see this instance as when `--name` isn't given
+pub fn default_name() -> String {
+    std::env::var("USER").ok().filter(|n| !n.is_empty()).unwrap_or_else(|| "racer".to_string())
+}
+
+/// One line of the protocol
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Message {
+    /// Joiner → host, first thing after connecting
+    Join { name: String },
+    /// Host → joiner: the id its progress shows up under
+    Welcome { id: usize },
+    /// Host → joiners: the text of the next race
+    Snippet { id: String, snippet: Snippet },
+    /// Host → joiners: the race is on, typed under the host's rules
+    Start { settings: Settings },
+    /// Joiner → host, whenever it moves on. A racer who failed (sudden
+    /// death) is out rather than finished.
+    Progress { progress: f64, wpm: f64, finished: bool, out: bool },
+    /// Host → joiners: everyone in the race
+    Standings { racers: Vec<Racer> },
+}
+
+/// Someone in the race, as the standings show them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Racer {
+    pub id: usize,
+    pub name: String,
+    /// From 0 to 1
+    pub progress: f64,
+    pub wpm: f64,
+    /// Finishing position, once finished
+    pub place: Option<usize>,
+    /// Failed before the end
+    pub out: bool,
+}
+
+impl Racer {
+    fn new(id: usize, name: String) -> Racer {
+        Racer { id, name, progress: 0.0, wpm: 0.0, place: None, out: false }
+    }
+}
+
+/// What the network threads hand to the UI thread. Joiners only ever
+/// talk to the host, which they know as `HOST_ID`.
+enum Event {
+    Connected(usize, Outbox),
+    Received(usize, Message),
+    Closed(usize),
+}
+
+/// What the app has to act on after a `poll`
+#[derive(Debug)]
+pub enum Update {
+    /// The host picked the snippet for the next race
+    Snippet(Snippet),
+    /// The race is on, under these rules
+    Start(Settings),
+}
+
+enum Role {
+    /// Connections to everyone who joined, by racer id
+    Host { peers: BTreeMap<usize, Outbox> },
+    Joiner { host: Outbox },
+}
+
+/// This instance's side of a race
+pub struct Race {
+    role: Role,
+    events: Receiver<Event>,
+    /// The racer id this instance shows up under
+    pub me: usize,
+    /// Everyone in the race, in joining order
+    pub racers: Vec<Racer>,
+    /// Where the race is: the port the host listens on, or the host's address
+    pub address: String,
+    /// The snippet being raced, sent to anyone who joins
+    snippet: Option<Snippet>,
+    /// Set once the connection to the host is gone
+    pub error: Option<String>,
+}
+
+impl Race {
+    /// Listens for joiners on `port` of every interface
+    pub fn host(port: u16, name: String) -> io::Result<Race> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let address = listener.local_addr()?.port().to_string();
+        let (events, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for (id, stream) in (HOST_ID + 1..).zip(listener.incoming()) {
+                let Ok(stream) = stream else { continue };
+                let _ = stream.set_nodelay(true);
+                let Ok(reader) = stream.try_clone() else { continue };
+                let Ok(outbox) = Outbox::new(stream) else { continue };
+                if events.send(Event::Connected(id, outbox)).is_err() {
+                    return;
+                }
+                let events = events.clone();
+                thread::spawn(move || read_messages(id, reader, events));
+            }
+        });
+        Ok(Race {
+            role: Role::Host { peers: BTreeMap::new() },
+            events: receiver,
+            me: HOST_ID,
+            racers: vec![Racer::new(HOST_ID, name)],
+            address,
+            snippet: None,
+            error: None,
+        })
+    }
+
+    /// Connects to a host at `address`, `host:port` or just `host`
+    pub fn join(address: &str, name: String) -> io::Result<Race> {
+        let target = if address.contains(':') { address.to_string() } else { format!("{}:{}", address, DEFAULT_PORT) };
+        let addr = target.to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no address for {}", target)))?;
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        let reader = stream.try_clone()?;
+        let host = Outbox::new(stream)?;
+        host.send(&Message::Join { name });
+        let (events, receiver) = mpsc::channel();
+        thread::spawn(move || read_messages(HOST_ID, reader, events));
+        Ok(Race {
+            role: Role::Joiner { host },
+            events: receiver,
+            me: HOST_ID,
+            racers: Vec::new(),
+            address: target,
+            snippet: None,
+            error: None,
+        })
+    }
+
+    pub fn is_host(&self) -> bool {
+        matches!(self.role, Role::Host { .. })
+    }
+
+    /// Whether a joiner has heard what to race yet
+    pub fn has_snippet(&self) -> bool {
+        self.snippet.is_some()
+    }
+
+    /// Handles everything that arrived since the last poll, without waiting
+    pub fn poll(&mut self) -> Vec<Update> {
+        let mut updates = Vec::new();
+        let mut changed = false;
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Event::Connected(id, outbox) => {
+                    if let Role::Host { peers } = &mut self.role {
+                        peers.insert(id, outbox);
+                    }
+                }
+                Event::Received(id, message) => {
+                    changed |= self.receive(id, message, &mut updates);
+                }
+                Event::Closed(id) => {
+                    if self.is_host() {
+                        self.drop_peer(id);
+                        changed = true;
+                    } else {
+                        self.error = Some("the host left the race".to_string());
+                    }
+                }
+            }
+        }
+        if changed {
+            self.broadcast_standings();
+        }
+        updates
+    }
+
+    /// Acts on one message; true when the standings changed
+    fn receive(&mut self, from: usize, message: Message, updates: &mut Vec<Update>) -> bool {
+        match message {
+            // A peer joins once; any further joins from it are ignored
+            Message::Join { name } if self.is_host() && !self.racers.iter().any(|r| r.id == from) => {
+                self.racers.push(Racer::new(from, name));
+                self.send_to(from, &Message::Welcome { id: from });
+                if let Some(snippet) = self.snippet.clone() {
+                    self.send_to(from, &Message::Snippet { id: snippet.id.clone(), snippet });
+                }
+                true
+            }
+            Message::Progress { progress, wpm, finished, out } if self.is_host() => {
+                self.update(from, progress, wpm, finished, out);
+                true
+            }
+            Message::Welcome { id } => {
+                self.me = id;
+                false
+            }
+            Message::Snippet { id, mut snippet } => {
+                // The id isn't part of a snippet's serialized form
+                snippet.id = id;
+                self.snippet = Some(snippet.clone());
+                updates.push(Update::Snippet(snippet));
+                false
+            }
+            Message::Start { settings } => {
+                updates.push(Update::Start(settings));
+                false
+            }
+            Message::Standings { racers } => {
+                self.racers = racers;
+                false
+            }
+            // Only the host takes joins and progress
+            _ => false,
+        }
+    }
+
+    /// Sets what the next race types and tells everyone (host only)
+    pub fn set_snippet(&mut self, snippet: &Snippet) {
+        self.snippet = Some(snippet.clone());
+        self.broadcast(&Message::Snippet { id: snippet.id.clone(), snippet: snippet.clone() });
+    }
+
+    /// Starts everyone on the current snippet from zero, typing under
+    /// `settings` (host only)
+    pub fn start(&mut self, settings: Settings) {
+        for racer in &mut self.racers {
+            racer.progress = 0.0;
+            racer.wpm = 0.0;
+            racer.place = None;
+            racer.out = false;
+        }
+        self.broadcast(&Message::Start { settings });
+        self.broadcast_standings();
+    }
+
+    /// Reports this instance's own progress to the rest of the race
+    pub fn report(&mut self, progress: f64, wpm: f64, finished: bool, out: bool) {
+        let message = Message::Progress { progress, wpm, finished, out };
+        let Some(racer) = self.racers.iter().find(|r| r.id == self.me) else {
+            // A joiner before its first standings still reports
+            if let Role::Joiner { host } = &self.role {
+                host.send(&message);
+            }
+            return;
+        };
+        if racer.progress == progress && racer.wpm == wpm && racer.place.is_some() == finished && racer.out == out {
+            return;
+        }
+        self.update(self.me, progress, wpm, finished, out);
+        match &self.role {
+            Role::Host { .. } => self.broadcast_standings(),
+            Role::Joiner { host } => host.send(&message),
+        }
+    }
+
+    fn update(&mut self, id: usize, progress: f64, wpm: f64, finished: bool, out: bool) {
+        let placed = self.racers.iter().filter(|r| r.place.is_some()).count();
+        let Some(racer) = self.racers.iter_mut().find(|r| r.id == id) else { return };
+        racer.progress = progress;
+        racer.wpm = wpm;
+        racer.out = out;
+        // A joiner's own finish shows straight away; the host's standings
+        // overwrite it with the real place
+        if finished && racer.place.is_none() {
+            racer.place = Some(placed + 1);
+        }
+    }
+
+    fn drop_peer(&mut self, id: usize) {
+        if let Role::Host { peers } = &mut self.role {
+            peers.remove(&id);
+        }
+        self.racers.retain(|r| r.id != id);
+    }
+
+    fn broadcast_standings(&mut self) {
+        if self.is_host() {
+            self.broadcast(&Message::Standings { racers: self.racers.clone() });
+        }
+    }
+
+    /// Sends to every joiner. One that can't keep up is hung up on by its
+    /// writer, and drops out when its reader sees the connection close.
+    fn broadcast(&self, message: &Message) {
+        if let Role::Host { peers } = &self.role {
+            for outbox in peers.values() {
+                outbox.send(message);
+            }
+        }
+    }
+
+    fn send_to(&self, id: usize, message: &Message) {
+        if let Role::Host { peers } = &self.role
+            && let Some(outbox) = peers.get(&id)
+        {
+            outbox.send(message);
+        }
+    }
+}
+
+/// The sending side of a connection. Lines are written on a thread of
+/// their own, so a peer that stops reading can't stall the UI.
+struct Outbox {
+    lines: Sender<String>,
+    stream: TcpStream,
+}
+
+impl Outbox {
+    fn new(stream: TcpStream) -> io::Result<Outbox> {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let mut writer = stream.try_clone()?;
+        let (lines, queue) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            for line in queue {
+                if writer.write_all(line.as_bytes()).is_err() {
+                    // The reader thread sees the close and reports it
+                    let _ = writer.shutdown(Shutdown::Both);
+                    return;
+                }
+            }
+        });
+        Ok(Outbox { lines, stream })
+    }
+
+    /// Queues `message`; once the writer has given up, it goes nowhere
+    fn send(&self, message: &Message) {
+        if let Ok(mut line) = serde_json::to_string(message) {
+            line.push('\n');
+            let _ = self.lines.send(line);
+        }
+    }
+}
+
+impl Drop for Outbox {
+    /// Hangs up, so the reader thread's clone of the connection doesn't
+    /// keep it open
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Runs on its own thread, passing on each line from `stream` until it
+/// closes or sends a line longer than `MAX_LINE`
+fn read_messages(id: usize, stream: TcpStream, events: Sender<Event>) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.by_ref().take(MAX_LINE as u64 + 1).read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) if !line.ends_with('\n') => {
+                // Too long, or cut off by the close
+                let _ = reader.get_ref().shutdown(Shutdown::Both);
+                break;
+            }
+            Ok(_) => {}
+        }
+        // Lines that don't parse are skipped, so newer peers can add messages
+        let Ok(message) = serde_json::from_str(&line) else { continue };
+        if events.send(Event::Received(id, message)).is_err() {
+            return;
+        }
+    }
+    let _ = events.send(Event::Closed(id));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use rhonetyping::engine::ErrorPolicy;
+
+    use super::*;
+    use crate::app::tests::snippet;
+    use crate::snippets::Language;
+
+    /// Polls the host and joiners until `done` holds, returning every
+    /// update the joiners got; gives up after a few seconds
+    fn exchange(host: &mut Race, joiners: &mut [Race], done: impl Fn(&Race, &[Race], &[Update]) -> bool) -> Vec<Update> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut updates = Vec::new();
+        loop {
+            host.poll();
+            for joiner in joiners.iter_mut() {
+                updates.extend(joiner.poll());
+            }
+            if done(host, joiners, &updates) {
+                return updates;
+            }
+            assert!(Instant::now() < deadline, "race never settled");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn join(host: &Race, name: &str) -> Race {
+        Race::join(&format!("127.0.0.1:{}", host.address), name.to_string()).unwrap()
+    }
+
+    #[test]
+    fn racers_share_the_snippet_start_and_standings() {
+        let mut host = Race::host(0, "ana".to_string()).unwrap();
+        host.set_snippet(&snippet(Language::Rust, "fn main() {}"));
+        let mut joiners = vec![join(&host, "bo"), join(&host, "cy")];
+
+        let updates = exchange(&mut host, &mut joiners, |_, joiners, updates| {
+            joiners.iter().all(|j| j.racers.len() == 3) && updates.len() == 2
+        });
+        let ids: Vec<usize> = joiners.iter().map(|j| j.me).collect();
+        assert!(ids.contains(&1) && ids.contains(&2));
+        let texts: Vec<&Snippet> = updates.iter()
+            .filter_map(|u| match u { Update::Snippet(s) => Some(s), Update::Start(_) => None })
+            .collect();
+        assert_eq!(texts.len(), 2);
+        assert!(texts.iter().all(|s| s.id == "test/snippet" && s.text == "fn main() {}"));
+
+        host.start(Settings { error_policy: ErrorPolicy::SuddenDeath, ..Settings::default() });
+        let updates = exchange(&mut host, &mut joiners, |_, _, updates| {
+            updates.iter().filter(|u| matches!(u, Update::Start(_))).count() == 2
+        });
+        // Everyone races under the host's rules
+        assert!(updates.iter().all(|u| matches!(u, Update::Start(s) if s.error_policy == ErrorPolicy::SuddenDeath)));
+
+        // The first joiner fails halfway, the second finishes first, then the host
+        joiners[0].report(0.5, 60.0, false, true);
+        joiners[1].report(1.0, 80.0, true, false);
+        exchange(&mut host, &mut joiners, |host, _, _| {
+            host.racers.iter().any(|r| r.place == Some(1)) && host.racers.iter().any(|r| r.progress == 0.5)
+        });
+        host.report(1.0, 70.0, true, false);
+        exchange(&mut host, &mut joiners, |_, joiners, _| {
+            joiners[0].racers.iter().any(|r| r.id == HOST_ID && r.place == Some(2))
+        });
+        let standings = &joiners[0].racers;
+        let cy = standings.iter().find(|r| r.name == "cy").unwrap();
+        assert_eq!((cy.place, cy.wpm), (Some(1), 80.0));
+        let bo = standings.iter().find(|r| r.name == "bo").unwrap();
+        assert_eq!((bo.place, bo.progress, bo.out), (None, 0.5, true));
+
+        // Whoever leaves drops out of the standings
+        joiners.pop();
+        exchange(&mut host, &mut joiners, |host, joiners, _| host.racers.len() == 2 && joiners[0].racers.len() == 2);
+    }
+
+    #[test]
+    fn joiners_hear_when_the_host_leaves() {
+        let mut host = Race::host(0, "ana".to_string()).unwrap();
+        let mut joiners = vec![join(&host, "bo")];
+        exchange(&mut host, &mut joiners, |_, joiners, _| joiners[0].racers.len() == 2);
+
+        drop(host);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while joiners[0].error.is_none() {
+            joiners[0].poll();
+            assert!(Instant::now() < deadline, "joiner never noticed");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn a_joiner_that_stops_reading_cant_stall_the_host() {
+        let mut host = Race::host(0, "ana".to_string()).unwrap();
+        let mut stalled = TcpStream::connect(("127.0.0.1", host.address.parse().unwrap())).unwrap();
+        stalled.write_all(b"{\"type\":\"join\",\"name\":\"zz\"}\n").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while host.racers.len() < 2 {
+            host.poll();
+            assert!(Instant::now() < deadline, "joiner never arrived");
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        // Far more than the socket buffers hold, none of it read
+        let text = "x".repeat(64 * 1024);
+        let started = Instant::now();
+        for _ in 0..100 {
+            host.set_snippet(&snippet(Language::English, &text));
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    /// Connects to `host` without the client side, to send raw lines
+    fn connect(host: &Race) -> TcpStream {
+        TcpStream::connect(("127.0.0.1", host.address.parse().unwrap())).unwrap()
+    }
+
+    #[test]
+    fn a_peer_only_joins_once() {
+        let mut host = Race::host(0, "ana".to_string()).unwrap();
+        let mut peer = connect(&host);
+        peer.write_all(b"{\"type\":\"join\",\"name\":\"zz\"}\n{\"type\":\"join\",\"name\":\"zz\"}\n").unwrap();
+        peer.write_all(b"{\"type\":\"progress\",\"progress\":0.5,\"wpm\":1.0,\"finished\":false,\"out\":false}\n").unwrap();
+        exchange(&mut host, &mut [], |host, _, _| host.racers.iter().any(|r| r.progress == 0.5));
+        assert_eq!(host.racers.len(), 2);
+    }
+
+    #[test]
+    fn a_peer_sending_an_endless_line_is_hung_up_on() {
+        let mut host = Race::host(0, "ana".to_string()).unwrap();
+        let mut peer = connect(&host);
+        peer.write_all(b"{\"type\":\"join\",\"name\":\"zz\"}\n").unwrap();
+        exchange(&mut host, &mut [], |host, _, _| host.racers.len() == 2);
+
+        // The host hangs up partway, so the writes may start failing
+        let chunk = vec![b'x'; 64 * 1024];
+        for _ in 0..(2 * MAX_LINE / chunk.len()) {
+            if peer.write_all(&chunk).is_err() {
+                break;
+            }
+        }
+        exchange(&mut host, &mut [], |host, _, _| host.racers.len() == 1);
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use rhonetyping::engine::{Input, Key, Replay, Session, Settings};
+
+use crate::snippets::Language;
+
+pub const RECORDINGS_DIR: &str = "recordings";
+
+/// How many recordings are kept before the oldest are deleted
+pub const MAX_RECORDINGS: usize = 200;
+
+/// Every key of one finished session with its timing, stored as
+/// `recordings/<id>.json` in the XDG data directory next to the history
+/// file. The session's `SessionRecord` holds the id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    /// Unique per recording, from `Recordings::next_id`
+    pub id: u64,
+    /// Unix timestamp (seconds) of when the session finished
+    pub timestamp: u64,
+    pub language: Language,
+    /// What the session was typing, as shown in the replay heading
+    pub title: String,
+    /// The whole text, including any stream appended in timed mode
+    pub text: String,
+    pub settings: Settings,
+    /// Each key with the milliseconds since the first keystroke
+    pub keys: Vec<(u64, Key)>,
+}
+
+impl Recording {
+    pub fn new(id: u64, timestamp: u64, language: Language, title: String, session: &Session) -> Recording {
+        Recording {
+            id,
+            timestamp,
+            language,
+            title,
+            text: session.chars().iter().collect(),
+            settings: session.settings(),
+            keys: session.inputs().iter().map(|i| (i.at.as_millis() as u64, i.key)).collect(),
+        }
+    }
+
+    /// A fresh playback of the recording from its start
+    pub fn replay(&self) -> Replay {
+        let inputs = self.keys.iter()
+            .map(|(ms, key)| Input { at: Duration::from_millis(*ms), key: *key })
+            .collect();
+        Replay::new(&self.text, self.settings, inputs)
+    }
+}
+
+/// The recordings on disk, indexed by id so screens can tell which history
+/// entries have one without touching the filesystem
+#[derive(Debug, Default)]
+pub struct Recordings {
+    saved: BTreeSet<u64>,
+    dir: Option<PathBuf>,
+}
+
+impl Recordings {
+    /// Indexes the recordings saved in `dir`; without one nothing can be saved
+    pub fn open(dir: Option<PathBuf>) -> Recordings {
+        let saved = dir.as_ref()
+            .and_then(|d| fs::read_dir(d).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let name = e.file_name().into_string().ok()?;
+                name.strip_suffix(".json")?.parse().ok()
+            })
+            .collect();
+        Recordings { saved, dir }
+    }
+
+    /// An id no saved recording has: the Unix time in milliseconds, bumped
+    /// past the newest id when sessions finish within the same millisecond
+    pub fn next_id(&self, now_millis: u64) -> u64 {
+        self.saved.last().map_or(now_millis, |last| now_millis.max(last + 1))
+    }
+
+    pub fn has(&self, id: u64) -> bool {
+        self.saved.contains(&id)
+    }
+
+    pub fn get(&self, id: u64) -> io::Result<Recording> {
+        let text = fs::read_to_string(self.path(id)?)?;
+        serde_json::from_str(&text).map_err(io::Error::other)
+    }
+
+    pub fn save(&mut self, recording: &Recording) -> io::Result<()> {
+        let path = self.path(recording.id)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string(recording).map_err(io::Error::other)?;
+        fs::write(path, json)?;
+        self.saved.insert(recording.id);
+        Ok(())
+    }
+
+    /// Deletes the oldest recordings beyond `MAX_RECORDINGS`, sparing the
+    /// ids in `keep`
+    pub fn prune(&mut self, keep: &BTreeSet<u64>) -> io::Result<()> {
+        let excess = self.saved.len().saturating_sub(MAX_RECORDINGS);
+        let oldest: Vec<u64> = self.saved.iter()
+            .filter(|id| !keep.contains(id))
+            .take(excess)
+            .copied()
+            .collect();
+        for id in oldest {
+            match fs::remove_file(self.path(id)?) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            self.saved.remove(&id);
+        }
+        Ok(())
+    }
+
+    fn path(&self, id: u64) -> io::Result<PathBuf> {
+        let dir = self.dir.as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory (HOME unset)"))?;
+        Ok(dir.join(format!("{}.json", id)))
+    }
+}
+
+/// Playback speeds offered on the Replay screen
+pub const SPEEDS: &[f64] = &[0.5, 1.0, 2.0, 4.0, 8.0];
+const NORMAL_SPEED: usize = 1;
+
+/// A recording being played back in real time, or sped up
+#[derive(Debug, Clone)]
+pub struct Playback {
+    pub recording: Recording,
+    pub replay: Replay,
+    /// Playback time since the first keystroke
+    pub position: Duration,
+    /// Index into `SPEEDS`
+    pub speed: usize,
+    pub paused: bool,
+    /// Clock time of the last tick
+    last_tick: Duration,
+}
+
+impl Playback {
+    pub fn new(recording: Recording, now: Duration) -> Playback {
+        Playback {
+            replay: recording.replay(),
+            recording,
+            position: Duration::ZERO,
+            speed: NORMAL_SPEED,
+            paused: false,
+            last_tick: now,
+        }
+    }
+
+    /// Moves playback on by the clock time since the last tick, scaled by
+    /// the speed, stopping at the end of the recording
+    pub fn tick(&mut self, now: Duration) {
+        let passed = now.saturating_sub(self.last_tick);
+        self.last_tick = now;
+        if self.paused || self.replay.is_done() {
+            return;
+        }
+        self.position = (self.position + passed.mul_f64(SPEEDS[self.speed])).min(self.replay.duration());
+        self.replay.advance_to(self.position);
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    /// Plays the recording again from the first keystroke
+    pub fn restart(&mut self) {
+        self.replay = self.recording.replay();
+        self.position = Duration::ZERO;
+        self.paused = false;
+    }
+
+    /// How far through the recording playback is, from 0 to 1
+    pub fn progress(&self) -> f64 {
+        let duration = self.replay.duration().as_secs_f64();
+        if duration == 0.0 { return 1.0; }
+        (self.position.as_secs_f64() / duration).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rhonetyping::engine::ErrorPolicy;
+
+    #[test]
+    fn recording_survives_a_round_trip() {
+        let settings = Settings { error_policy: ErrorPolicy::Word, ..Settings::default() };
+        let mut session = Session::new("ab\ncd", settings);
+        let keys = [Key::Char('a'), Key::Char('x'), Key::Backspace, Key::Char('b'), Key::Char('\n'), Key::Tab];
+        for (i, key) in keys.into_iter().enumerate() {
+            session.key(key, Duration::from_millis(100 * i as u64));
+        }
+        let recording = Recording::new(1, 1, Language::English, "test".to_string(), &session);
+
+        let json = serde_json::to_string(&recording).unwrap();
+        let loaded: Recording = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.keys, recording.keys);
+        let mut replay = loaded.replay();
+        replay.advance_to(Duration::from_secs(1));
+        assert_eq!(replay.session().states(), session.states());
+        assert_eq!(replay.session().cursor(), session.cursor());
+    }
+
+    #[test]
+    fn recordings_get_unique_ids_and_the_oldest_are_pruned() {
+        let dir = std::env::temp_dir().join(format!("rhonetyping-recordings-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut recordings = Recordings::open(Some(dir.clone()));
+        let session = Session::new("ab", Settings::default());
+
+        // Sessions finishing in the same millisecond still get their own file
+        for _ in 0..=MAX_RECORDINGS {
+            let id = recordings.next_id(1000);
+            recordings.save(&Recording::new(id, 1, Language::English, "test".to_string(), &session)).unwrap();
+        }
+        assert!(recordings.has(1000) && recordings.has(1000 + MAX_RECORDINGS as u64));
+        assert_eq!(recordings.next_id(5000), 5000);
+
+        recordings.prune(&BTreeSet::from([1000])).unwrap();
+        assert!(recordings.has(1000));
+        assert!(!recordings.has(1001));
+        assert_eq!(Recordings::open(Some(dir.clone())).saved.len(), MAX_RECORDINGS);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -1,7 +1,13 @@
+use std::fs;
+
 use rand::seq::SliceRandom;
 use rand::thread_rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+use crate::paths;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Language {
     English,
     Rust,
@@ -16,227 +22,184 @@ impl Language {
             Language::Python => "Python",
         }
     }
-}
 
-pub const MENU_OPTIONS: &[Language] = &[Language::English, Language::Rust, Language::Python];
+    /// Parses a language name as written in config files and on the command line
+    pub fn from_name(name: &str) -> Option<Language> {
+        LANGUAGES.iter().copied().find(|l| l.label().eq_ignore_ascii_case(name))
+    }
 
-pub fn random_snippet(lang: Language) -> String {
-    let pool = match lang {
-        Language::English => ENGLISH,
-        Language::Rust => RUST_SNIPPETS,
-        Language::Python => PYTHON_SNIPPETS,
-    };
-    let snippet = pool.choose(&mut thread_rng()).unwrap();
-    snippet.trim_matches('\n').to_string()
+    /// Maps a file extension to the language its contents are practised as
+    pub fn from_extension(ext: &str) -> Option<Language> {
+        match ext {
+            "txt" | "md" => Some(Language::English),
+            "rs" => Some(Language::Rust),
+            "py" | "pyi" => Some(Language::Python),
+            _ => None,
+        }
+    }
 }
 
-const ENGLISH: &[&str] = &[
-    "the quick brown fox jumps over the lazy dog near the riverbank",
-    "practice makes perfect and consistency is the key to mastery",
-    "a journey of a thousand miles begins with a single step forward",
-    "the only way to do great work is to love what you do every day",
-    "simplicity is the ultimate sophistication in both design and code",
-    "every expert was once a beginner who refused to give up on learning",
-    "typing fast is not about speed alone but about accuracy and rhythm",
-    "the best time to plant a tree was twenty years ago the second best time is now",
-    "focus on being productive instead of busy and results will follow naturally",
-    "do not wait to strike until the iron is hot but make it hot by striking",
-];
-
-const RUST_SNIPPETS: &[&str] = &[
-    r#"fn main() {
-    let numbers = vec![1, 2, 3, 4, 5];
-    let sum: i32 = numbers.iter().sum();
-    println!("Sum: {}", sum);
-}"#,
-    r#"fn fibonacci(n: u64) -> u64 {
-    match n {
-        0 => 0,
-        1 => 1,
-        _ => fibonacci(n - 1) + fibonacci(n - 2),
-    }
-}"#,
-    r#"use std::collections::HashMap;
+pub const LANGUAGES: &[Language] = &[Language::English, Language::Rust, Language::Python];
 
-fn word_count(text: &str) -> HashMap<&str, usize> {
-    let mut map = HashMap::new();
-    for word in text.split_whitespace() {
-        *map.entry(word).or_insert(0) += 1;
-    }
-    map
-}"#,
-    r#"struct Stack<T> {
-    elements: Vec<T>,
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
 }
 
-impl<T> Stack<T> {
-    fn new() -> Self {
-        Stack { elements: Vec::new() }
+impl Difficulty {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
     }
+}
 
-    fn push(&mut self, item: T) {
-        self.elements.push(item);
-    }
+/// A practice text together with its metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    /// Stable identifier used to match history entries, e.g. `Built-in/rust/fibonacci`
+    #[serde(skip)]
+    pub id: String,
+    pub title: String,
+    pub language: Language,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Attribution: where the text was taken from
+    #[serde(default)]
+    pub source: Option<String>,
+    pub text: String,
+}
 
-    fn pop(&mut self) -> Option<T> {
-        self.elements.pop()
-    }
-}"#,
-    r#"fn is_palindrome(s: &str) -> bool {
-    let chars: Vec<char> = s.chars().collect();
-    let len = chars.len();
-    for i in 0..len / 2 {
-        if chars[i] != chars[len - 1 - i] {
-            return false;
+/// A named collection of snippets, loaded from a TOML file:
+///
+/// ```toml
+/// name = "My pack"
+///
+/// [[snippet]]
+/// title = "hello"
+/// language = "rust"        # english | rust | python
+/// difficulty = "easy"      # optional: easy | medium | hard
+/// tags = ["basics"]        # optional
+/// source = "The Book"      # optional attribution
+/// text = '''
+/// fn main() {}'''
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct Pack {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(rename = "snippet", default)]
+    pub snippets: Vec<Snippet>,
+}
+
+impl Pack {
+    pub fn parse(text: &str) -> Result<Pack, String> {
+        let mut pack: Pack = toml::from_str(text).map_err(|e| e.message().to_string())?;
+        for snippet in &mut pack.snippets {
+            snippet.id = format!("{}/{}/{}", pack.name, snippet.language.label().to_lowercase(), snippet.title);
+            snippet.text = snippet.text.replace("\r\n", "\n").trim_matches('\n').to_string();
+        }
+        pack.snippets.retain(|s| !s.text.is_empty());
+        if pack.snippets.is_empty() {
+            return Err("pack has no snippets".to_string());
         }
+        Ok(pack)
     }
-    true
-}"#,
-    r#"use std::fmt;
-
-#[derive(Debug)]
-enum Direction {
-    North,
-    South,
-    East,
-    West,
 }
 
-impl fmt::Display for Direction {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
-    }
-}"#,
-    r#"fn largest<T: PartialOrd>(list: &[T]) -> &T {
-    let mut largest = &list[0];
-    for item in list {
-        if item > largest {
-            largest = item;
+const DEFAULT_PACK: &str = include_str!("../packs/default.toml");
+
+/// What a menu entry starts a session with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuOption {
+    /// Snippets of one language from the built-in pack
+    Language(Language),
+    /// Any snippet from a user pack (index into `Library::packs`)
+    Pack(usize),
+    /// Generated practice for the user's weakest keys
+    Drill,
+}
+
+/// The built-in pack plus every user pack found in the config directory
+pub struct Library {
+    pub builtin: Pack,
+    pub packs: Vec<Pack>,
+}
+
+impl Library {
+    /// Loads all packs. Broken user packs are skipped and reported as
+    /// warnings instead of failing startup.
+    pub fn load() -> (Library, Vec<String>) {
+        let mut library = Library::builtin();
+        let mut warnings = Vec::new();
+
+        let dir = paths::config_dir().map(|d| d.join("packs"));
+        let mut files: Vec<_> = dir
+            .and_then(|d| fs::read_dir(d).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+            .collect();
+        files.sort();
+
+        for path in files {
+            let parsed = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| Pack::parse(&text));
+            match parsed {
+                Ok(pack) => library.packs.push(pack),
+                Err(e) => warnings.push(format!("skipped snippet pack {}: {}", path.display(), e)),
+            }
         }
+
+        (library, warnings)
     }
-    largest
-}"#,
-    r#"fn flatten(nested: Vec<Vec<i32>>) -> Vec<i32> {
-    nested.into_iter().flatten().collect()
-}
 
-fn main() {
-    let data = vec![vec![1, 2], vec![3, 4], vec![5]];
-    println!("{:?}", flatten(data));
-}"#,
-    r#"trait Animal {
-    fn name(&self) -> &str;
-    fn sound(&self) -> &str;
+    /// Just the built-in pack
+    pub fn builtin() -> Library {
+        let builtin = Pack::parse(DEFAULT_PACK).expect("built-in snippet pack is valid");
+        Library { builtin, packs: Vec::new() }
+    }
 
-    fn describe(&self) {
-        println!("{} says {}", self.name(), self.sound());
+    pub fn menu_options(&self) -> Vec<MenuOption> {
+        LANGUAGES.iter().map(|l| MenuOption::Language(*l))
+            .chain((0..self.packs.len()).map(MenuOption::Pack))
+            .chain([MenuOption::Drill])
+            .collect()
     }
-}
 
-struct Dog;
-
-impl Animal for Dog {
-    fn name(&self) -> &str { "Dog" }
-    fn sound(&self) -> &str { "woof" }
-}"#,
-    r#"fn merge_sorted(a: &[i32], b: &[i32]) -> Vec<i32> {
-    let mut result = Vec::with_capacity(a.len() + b.len());
-    let (mut i, mut j) = (0, 0);
-    while i < a.len() && j < b.len() {
-        if a[i] <= b[j] { result.push(a[i]); i += 1; }
-        else { result.push(b[j]); j += 1; }
+    pub fn label(&self, option: MenuOption) -> &str {
+        match option {
+            MenuOption::Language(lang) => lang.label(),
+            MenuOption::Pack(i) => &self.packs[i].name,
+            MenuOption::Drill => "Drill",
+        }
+    }
+
+    /// A random snippet for a language or pack entry. Drills are generated
+    /// from key stats instead, so they fall back to any built-in snippet.
+    pub fn random(&self, option: MenuOption) -> Snippet {
+        let mut rng = thread_rng();
+        let snippet = match option {
+            MenuOption::Language(lang) => {
+                let pool: Vec<&Snippet> = self.builtin.snippets.iter()
+                    .filter(|s| s.language == lang)
+                    .collect();
+                pool.choose(&mut rng).copied()
+            }
+            MenuOption::Pack(i) => self.packs[i].snippets.choose(&mut rng),
+            MenuOption::Drill => self.builtin.snippets.choose(&mut rng),
+        };
+        snippet.expect("every menu entry has snippets to draw from").clone()
     }
-    result.extend_from_slice(&a[i..]);
-    result.extend_from_slice(&b[j..]);
-    result
-}"#,
-];
-
-const PYTHON_SNIPPETS: &[&str] = &[
-    r#"def fibonacci(n):
-    if n <= 1:
-        return n
-    a, b = 0, 1
-    for _ in range(n - 1):
-        a, b = b, a + b
-    return b"#,
-    r#"from collections import Counter
-
-def most_common(words):
-    count = Counter(words)
-    return count.most_common(3)"#,
-    r#"def binary_search(arr, target):
-    left, right = 0, len(arr) - 1
-    while left <= right:
-        mid = (left + right) // 2
-        if arr[mid] == target:
-            return mid
-        elif arr[mid] < target:
-            left = mid + 1
-        else:
-            right = mid - 1
-    return -1"#,
-    r#"class Stack:
-    def __init__(self):
-        self.items = []
-
-    def push(self, item):
-        self.items.append(item)
-
-    def pop(self):
-        return self.items.pop() if self.items else None
-
-    def peek(self):
-        return self.items[-1] if self.items else None"#,
-    r#"def flatten(nested):
-    result = []
-    for item in nested:
-        if isinstance(item, list):
-            result.extend(flatten(item))
-        else:
-            result.append(item)
-    return result"#,
-    r#"import functools
-
-def memoize(func):
-    cache = {}
-    @functools.wraps(func)
-    def wrapper(*args):
-        if args not in cache:
-            cache[args] = func(*args)
-        return cache[args]
-    return wrapper"#,
-    r#"def quicksort(arr):
-    if len(arr) <= 1:
-        return arr
-    pivot = arr[len(arr) // 2]
-    left = [x for x in arr if x < pivot]
-    middle = [x for x in arr if x == pivot]
-    right = [x for x in arr if x > pivot]
-    return quicksort(left) + middle + quicksort(right)"#,
-    r#"from dataclasses import dataclass
-from typing import List
-
-@dataclass
-class Student:
-    name: str
-    grade: float
-
-def top_students(students: List[Student], n: int) -> List[Student]:
-    return sorted(students, key=lambda s: s.grade, reverse=True)[:n]"#,
-    r#"def is_prime(n):
-    if n < 2:
-        return False
-    for i in range(2, int(n ** 0.5) + 1):
-        if n % i == 0:
-            return False
-    return True
-
-primes = [n for n in range(2, 50) if is_prime(n)]"#,
-    r#"def word_frequency(text):
-    words = text.lower().split()
-    freq = {}
-    for word in words:
-        freq[word] = freq.get(word, 0) + 1
-    return dict(sorted(freq.items(), key=lambda x: x[1], reverse=True))"#,
-];
+}
@@ -0,0 +1,200 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rand::seq::IteratorRandom;
+use rand::thread_rng;
+
+use crate::snippets::{Language, Snippet};
+
+// ── Limits ───────────────────────────────────────────────────────────────────
+const MAX_FILE_BYTES: u64 = 256 * 1024;  // larger files are skipped entirely
+const MAX_SNIPPET_LINES: usize = 20;     // longer items are skipped, never cut
+const MIN_SNIPPET_CHARS: usize = 40;     // shorter chunks aren't worth a session
+const MAX_ENGLISH_CHARS: usize = 300;    // prose paragraphs are cut at a word boundary
+
+/// Directories that never contain anything worth practising on
+const SKIP_DIRS: &[&str] = &["target", "node_modules", "__pycache__", "venv"];
+
+/// Practice snippets extracted from user-supplied files and directories
+#[derive(Debug, Default)]
+pub struct SourceSnippets {
+    snippets: Vec<Snippet>,
+}
+
+impl SourceSnippets {
+    /// Walks every path (file or directory) and extracts snippets from
+    /// files whose extension maps to a `Language`. A path that can't be
+    /// read is an error; anything unreadable inside a directory is skipped
+    /// and reported as a warning.
+    pub fn load(paths: &[PathBuf]) -> io::Result<(Self, Vec<String>)> {
+        let mut files = Vec::new();
+        let mut warnings = Vec::new();
+        for path in paths {
+            collect_files(path, &mut files, &mut warnings)?;
+        }
+
+        let mut snippets = Vec::new();
+        for (language, path) in files {
+            let Some(text) = read_text(&path) else { continue };
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("?");
+            for (line, text) in chunk(language, &text) {
+                snippets.push(Snippet {
+                    id: format!("{}:{}", path.display(), line),
+                    title: format!("{}:{}", name, line),
+                    language,
+                    difficulty: Default::default(),
+                    tags: Vec::new(),
+                    source: Some(path.display().to_string()),
+                    text,
+                });
+            }
+        }
+        Ok((SourceSnippets { snippets }, warnings))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snippets.is_empty()
+    }
+
+    /// A random snippet for `lang`, if any were extracted for it
+    pub fn random(&self, lang: Language) -> Option<Snippet> {
+        self.snippets.iter()
+            .filter(|s| s.language == lang)
+            .choose(&mut thread_rng())
+            .cloned()
+    }
+}
+
+fn collect_files(path: &Path, out: &mut Vec<(Language, PathBuf)>, warnings: &mut Vec<String>) -> io::Result<()> {
+    let meta = fs::metadata(path)?;
+    if meta.is_dir() {
+        let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .collect();
+        entries.sort();
+        for entry in entries {
+            let name = entry.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            if name.starts_with('.') || SKIP_DIRS.contains(&name) {
+                continue;
+            }
+            // Unreadable entries inside a tree are skipped rather than fatal
+            if let Err(e) = collect_files(&entry, out, warnings) {
+                warnings.push(format!("skipped {}: {}", entry.display(), e));
+            }
+        }
+    } else if meta.len() <= MAX_FILE_BYTES {
+        let lang = path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(Language::from_extension);
+        if let Some(lang) = lang {
+            out.push((lang, path.to_path_buf()));
+        }
+    }
+    Ok(())
+}
+
+/// Reads a file as text, rejecting anything that looks binary
+fn read_text(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    if bytes.contains(&0) {
+        return None;
+    }
+    let text = String::from_utf8(bytes).ok()?;
+    Some(text.replace("\r\n", "\n"))
+}
+
+/// Splits a file into session-sized snippets, each paired with the
+/// (1-based) line it starts on
+fn chunk(lang: Language, text: &str) -> Vec<(usize, String)> {
+    match lang {
+        Language::English => chunk_prose(text),
+        Language::Rust | Language::Python => chunk_code(text),
+    }
+}
+
+/// Prose: one snippet per paragraph, flattened onto a single line
+fn chunk_prose(text: &str) -> Vec<(usize, String)> {
+    let mut paragraphs: Vec<(usize, Vec<&str>)> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        match paragraphs.last_mut() {
+            _ if line.trim().is_empty() => paragraphs.push((i + 2, Vec::new())),
+            Some((_, words)) => words.extend(line.split_whitespace()),
+            None => paragraphs.push((i + 1, line.split_whitespace().collect())),
+        }
+    }
+
+    paragraphs.into_iter()
+        .map(|(line, words)| (line, words.join(" ")))
+        .filter(|(_, para)| para.is_ascii())
+        .map(|(line, para)| {
+            if para.len() <= MAX_ENGLISH_CHARS {
+                return (line, para);
+            }
+            let cut = para[..MAX_ENGLISH_CHARS].rfind(' ').unwrap_or(MAX_ENGLISH_CHARS);
+            (line, para[..cut].to_string())
+        })
+        .filter(|(_, para)| para.len() >= MIN_SNIPPET_CHARS)
+        .collect()
+}
+
+/// Code: split into top-level items (a non-indented line following a blank
+/// line starts a new item), so each snippet is roughly one function. Items
+/// too long for a session are left out rather than cut mid-body.
+fn chunk_code(text: &str) -> Vec<(usize, String)> {
+    let mut items: Vec<(usize, Vec<&str>)> = Vec::new();
+    let mut prev_blank = true;
+
+    for (i, line) in text.lines().map(str::trim_end).enumerate() {
+        let top_level = !line.is_empty() && !line.starts_with([' ', '\t']);
+        match items.last_mut() {
+            Some((_, lines)) if !(top_level && prev_blank) => lines.push(line),
+            _ => items.push((i + 1, vec![line])),
+        }
+        prev_blank = line.is_empty();
+    }
+
+    items.into_iter()
+        .filter_map(|(start, item)| {
+            let skipped = item.iter().take_while(|l| l.is_empty()).count();
+            let text = item.join("\n").trim_matches('\n').to_string();
+            (text.lines().count() <= MAX_SNIPPET_LINES).then_some((start + skipped, text))
+        })
+        .filter(|(_, s)| s.len() >= MIN_SNIPPET_CHARS && s.is_ascii())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_items_too_long_for_a_session_are_skipped_whole() {
+        let body: Vec<String> = (0..MAX_SNIPPET_LINES).map(|i| format!("    let x{} = {};", i, i)).collect();
+        let text = format!(
+            "fn long() {{\n{}\n}}\n\nfn short(a: u32, b: u32) -> u32 {{\n    a * b + a - b\n}}\n",
+            body.join("\n"),
+        );
+        let chunks = chunk_code(&text);
+        assert_eq!(chunks.len(), 1);
+        let (line, snippet) = &chunks[0];
+        assert_eq!(*line, MAX_SNIPPET_LINES + 4);
+        assert!(snippet.starts_with("fn short") && snippet.ends_with('}'));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_entries_in_a_tree_are_reported() {
+        let dir = std::env::temp_dir().join(format!("rhonetyping-source-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.py"), "def add(a, b):\n    return a + b  # plus a few more chars\n").unwrap();
+        std::os::unix::fs::symlink(dir.join("missing.py"), dir.join("broken.py")).unwrap();
+
+        let (sources, warnings) = SourceSnippets::load(std::slice::from_ref(&dir)).unwrap();
+        assert!(!sources.is_empty());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with(&format!("skipped {}", dir.join("broken.py").display())));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,234 @@
+use std::fs;
+use std::str::FromStr;
+
+use ratatui::style::Color;
+use serde::Deserialize;
+
+use crate::paths;
+
+/// Every colour the UI draws with
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    /// Regular text
+    pub fg: Color,
+    /// Untyped text, hints and labels
+    pub dim: Color,
+    pub correct: Color,
+    pub wrong: Color,
+    /// Cursor, selection and highlighted numbers
+    pub accent: Color,
+    pub bg: Color,
+    pub border: Color,
+    pub title: Color,
+
+    // Untyped code, kept close to `dim` so typed text still stands out
+    pub keyword: Color,
+    pub string: Color,
+    pub number: Color,
+    pub comment: Color,
+    pub punctuation: Color,
+}
+
+impl Theme {
+    /// The original warm dark palette
+    pub fn dark() -> Theme {
+        Theme {
+            name: "dark".to_string(),
+            fg: Color::Rgb(220, 215, 205),         // warm off-white
+            dim: Color::Rgb(90, 85, 80),           // untyped grey
+            correct: Color::Rgb(130, 195, 120),
+            wrong: Color::Rgb(210, 90, 80),
+            accent: Color::Rgb(220, 185, 80),
+            bg: Color::Rgb(22, 20, 18),
+            border: Color::Rgb(60, 55, 50),
+            title: Color::Rgb(200, 160, 90),       // warm gold
+            keyword: Color::Rgb(125, 100, 130),    // muted plum
+            string: Color::Rgb(105, 120, 85),      // muted olive
+            number: Color::Rgb(135, 105, 75),      // muted amber
+            comment: Color::Rgb(70, 68, 64),       // darker than dim
+            punctuation: Color::Rgb(110, 104, 98), // a shade above dim
+        }
+    }
+
+    pub fn light() -> Theme {
+        Theme {
+            name: "light".to_string(),
+            fg: Color::Rgb(45, 42, 38),
+            dim: Color::Rgb(160, 155, 148),
+            correct: Color::Rgb(50, 130, 60),
+            wrong: Color::Rgb(195, 50, 45),
+            accent: Color::Rgb(190, 120, 20),
+            bg: Color::Rgb(248, 245, 238),
+            border: Color::Rgb(205, 200, 190),
+            title: Color::Rgb(150, 95, 25),
+            keyword: Color::Rgb(160, 130, 170),
+            string: Color::Rgb(135, 155, 115),
+            number: Color::Rgb(180, 145, 110),
+            comment: Color::Rgb(195, 190, 182),
+            punctuation: Color::Rgb(140, 135, 128),
+        }
+    }
+
+    /// Pure black and white with saturated marks, for low vision or glare
+    pub fn high_contrast() -> Theme {
+        Theme {
+            name: "high contrast".to_string(),
+            fg: Color::Rgb(255, 255, 255),
+            dim: Color::Rgb(170, 170, 170),
+            correct: Color::Rgb(0, 255, 90),
+            wrong: Color::Rgb(255, 40, 40),
+            accent: Color::Rgb(255, 230, 0),
+            bg: Color::Rgb(0, 0, 0),
+            border: Color::Rgb(200, 200, 200),
+            title: Color::Rgb(255, 230, 0),
+            keyword: Color::Rgb(200, 160, 255),
+            string: Color::Rgb(160, 220, 140),
+            number: Color::Rgb(255, 180, 100),
+            comment: Color::Rgb(130, 130, 130),
+            punctuation: Color::Rgb(210, 210, 210),
+        }
+    }
+
+    /// Solarized dark
+    pub fn solarized() -> Theme {
+        Theme {
+            name: "solarized".to_string(),
+            fg: Color::Rgb(147, 161, 161),      // base1
+            dim: Color::Rgb(88, 110, 117),      // base01
+            correct: Color::Rgb(133, 153, 0),   // green
+            wrong: Color::Rgb(220, 50, 47),     // red
+            accent: Color::Rgb(181, 137, 0),    // yellow
+            bg: Color::Rgb(0, 43, 54),          // base03
+            border: Color::Rgb(7, 54, 66),      // base02
+            title: Color::Rgb(38, 139, 210),    // blue
+            keyword: Color::Rgb(108, 113, 196), // violet
+            string: Color::Rgb(42, 161, 152),   // cyan
+            number: Color::Rgb(203, 75, 22),    // orange
+            comment: Color::Rgb(7, 54, 66),     // base02
+            punctuation: Color::Rgb(101, 123, 131), // base00
+        }
+    }
+
+    /// Only the 16 ANSI colours, for terminals without true colour
+    pub fn monochrome() -> Theme {
+        Theme {
+            name: "monochrome".to_string(),
+            fg: Color::White,
+            dim: Color::DarkGray,
+            correct: Color::Gray,
+            wrong: Color::Red,
+            accent: Color::White,
+            bg: Color::Reset,
+            border: Color::DarkGray,
+            title: Color::White,
+            keyword: Color::DarkGray,
+            string: Color::DarkGray,
+            number: Color::DarkGray,
+            comment: Color::DarkGray,
+            punctuation: Color::DarkGray,
+        }
+    }
+
+    /// Text drawn on an `accent` block, like the cursor. A `Reset`
+    /// background would take the terminal's default colour, which can match
+    /// the block and hide the character.
+    pub fn on_accent(&self) -> Color {
+        if self.bg == Color::Reset { Color::Black } else { self.bg }
+    }
+
+    pub fn presets() -> Vec<Theme> {
+        vec![
+            Theme::dark(),
+            Theme::light(),
+            Theme::high_contrast(),
+            Theme::solarized(),
+            Theme::monochrome(),
+        ]
+    }
+
+    /// The presets followed by every user theme in `<config>/themes/*.toml`.
+    /// Broken theme files are skipped and reported as warnings.
+    ///
+    /// ```toml
+    /// name = "dracula"
+    /// fg = "#f8f8f2"           # hex, a colour name ("darkgray") or a 0-255 index
+    /// dim = "#6272a4"
+    /// correct = "#50fa7b"
+    /// wrong = "#ff5555"
+    /// accent = "#f1fa8c"
+    /// bg = "#282a36"
+    /// border = "#44475a"
+    /// title = "#bd93f9"
+    /// keyword = "#9580c0"      # optional code colours; default to `dim`
+    /// ```
+    pub fn load_all() -> (Vec<Theme>, Vec<String>) {
+        let mut themes = Theme::presets();
+        let mut warnings = Vec::new();
+
+        let dir = paths::config_dir().map(|d| d.join("themes"));
+        let mut files: Vec<_> = dir
+            .and_then(|d| fs::read_dir(d).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+            .collect();
+        files.sort();
+
+        for path in files {
+            let parsed = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| Theme::parse(&text));
+            match parsed {
+                Ok(theme) => themes.push(theme),
+                Err(e) => warnings.push(format!("skipped theme {}: {}", path.display(), e)),
+            }
+        }
+        (themes, warnings)
+    }
+
+    pub fn parse(text: &str) -> Result<Theme, String> {
+        let file: ThemeFile = toml::from_str(text).map_err(|e| e.message().to_string())?;
+        let dim = color(&file.dim)?;
+        let code = |c: &Option<String>| c.as_deref().map_or(Ok(dim), color);
+        Ok(Theme {
+            name: file.name,
+            fg: color(&file.fg)?,
+            dim,
+            correct: color(&file.correct)?,
+            wrong: color(&file.wrong)?,
+            accent: color(&file.accent)?,
+            bg: color(&file.bg)?,
+            border: color(&file.border)?,
+            title: color(&file.title)?,
+            keyword: code(&file.keyword)?,
+            string: code(&file.string)?,
+            number: code(&file.number)?,
+            comment: code(&file.comment)?,
+            punctuation: code(&file.punctuation)?,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct ThemeFile {
+    name: String,
+    fg: String,
+    dim: String,
+    correct: String,
+    wrong: String,
+    accent: String,
+    bg: String,
+    border: String,
+    title: String,
+    keyword: Option<String>,
+    string: Option<String>,
+    number: Option<String>,
+    comment: Option<String>,
+    punctuation: Option<String>,
+}
+
+fn color(value: &str) -> Result<Color, String> {
+    Color::from_str(value).map_err(|_| format!("invalid colour {:?}", value))
+}
@@ -1,29 +1,32 @@
+use std::time::Duration;
+
 use ratatui::{
     Frame,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
+    symbols::Marker,
     text::{Line, Span},
-    widgets::{Block, Borders, Gauge, Paragraph, Wrap},
+    widgets::{Axis, Block, Borders, Chart, Dataset, Gauge, GraphType, Paragraph, Wrap},
 };
 
-use crate::app::{App, CharState, Screen};
-use crate::snippets::MENU_OPTIONS;
+use rhonetyping::engine::{CharState, Session};
 
-// ── Palette ──────────────────────────────────────────────────────────────────
-const FG: Color        = Color::Rgb(220, 215, 205); // warm off-white
-const DIM: Color       = Color::Rgb(90, 85, 80);    // untyped grey
-const GREEN: Color     = Color::Rgb(130, 195, 120); // correct
-const RED: Color       = Color::Rgb(210, 90, 80);   // wrong
-const YELLOW: Color    = Color::Rgb(220, 185, 80);  // accent / cursor
-const BG: Color        = Color::Rgb(22, 20, 18);    // dark background
-const BORDER: Color    = Color::Rgb(60, 55, 50);    // subtle border
-const TITLE_FG: Color  = Color::Rgb(200, 160, 90);  // warm gold title
+use crate::app::{App, Mode, PREFERENCES, Preference, SETTINGS_ROWS, Screen};
+use crate::config::{Config, CursorStyle};
+use crate::highlight::{self, Token};
+use crate::history::{ROLLING_WINDOW, format_timestamp, is_record};
+use crate::keystats::{KeyStat, MIN_SAMPLES};
+use crate::race::Race;
+use crate::recording::SPEEDS;
+use crate::snippets::{Language, MenuOption};
+use crate::theme::Theme;
 
 pub fn draw(f: &mut Frame, app: &App) {
+    let t = app.theme();
     // Full-screen dark background
     let area = f.area();
     f.render_widget(
-        Block::default().style(Style::default().bg(BG)),
+        Block::default().style(Style::default().bg(t.bg)),
         area,
     );
 
@@ -31,20 +34,27 @@ pub fn draw(f: &mut Frame, app: &App) {
         Screen::Menu    => draw_menu(f, app, area),
         Screen::Typing  => draw_typing(f, app, area),
         Screen::Results => draw_results(f, app, area),
+        Screen::History => draw_history(f, app, area),
+        Screen::Stats   => draw_key_stats(f, app, area),
+        Screen::Themes  => draw_themes(f, app, area),
+        Screen::Settings => draw_settings(f, app, area),
+        Screen::Replay  => draw_replay(f, app, area),
+        Screen::Lobby   => draw_lobby(f, app, area),
     }
 }
 
 // ── Menu ─────────────────────────────────────────────────────────────────────
 
 fn draw_menu(f: &mut Frame, app: &App, area: Rect) {
-    let chunks = centered_rect(50, 60, area);
+    let t = app.theme();
+    let chunks = centered_rect(70, 60, area);
 
     let block = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(BORDER))
+        .border_style(Style::default().fg(t.border))
         .title(Span::styled(
             "  rhonetyping  ",
-            Style::default().fg(TITLE_FG).add_modifier(Modifier::BOLD),
+            Style::default().fg(t.title).add_modifier(Modifier::BOLD),
         ))
         .title_alignment(Alignment::Center);
 
@@ -52,17 +62,24 @@ fn draw_menu(f: &mut Frame, app: &App, area: Rect) {
 
     let inner = inner_rect(chunks, 2);
 
-    // Subtitle
-    let subtitle = Paragraph::new("select a mode and press enter")
-        .style(Style::default().fg(DIM))
+    // Subtitle: the selected pack's description, if it has one
+    let subtitle_text = match app.menu[app.selected_menu] {
+        MenuOption::Pack(i) if !app.library.packs[i].description.is_empty() => {
+            app.library.packs[i].description.as_str()
+        }
+        MenuOption::Drill => "practice text built around your weakest keys",
+        _ => "select a mode and press enter",
+    };
+    let subtitle = Paragraph::new(subtitle_text)
+        .style(Style::default().fg(t.dim))
         .alignment(Alignment::Center);
     f.render_widget(subtitle, inner);
 
     // Menu items - vertically centered
-    let item_height = MENU_OPTIONS.len() as u16 * 2;
+    let item_height = app.menu.len() as u16 * 2;
     let start_y = inner.y + (inner.height.saturating_sub(item_height)) / 2 + 2;
 
-    for (i, lang) in MENU_OPTIONS.iter().enumerate() {
+    for (i, option) in app.menu.iter().enumerate() {
         let y = start_y + i as u16 * 2;
         if y >= inner.y + inner.height { break; }
 
@@ -71,26 +88,67 @@ fn draw_menu(f: &mut Frame, app: &App, area: Rect) {
         let (prefix, style) = if i == app.selected_menu {
             (
                 "▶  ",
-                Style::default().fg(YELLOW).add_modifier(Modifier::BOLD),
+                Style::default().fg(t.accent).add_modifier(Modifier::BOLD),
             )
         } else {
             (
                 "   ",
-                Style::default().fg(DIM),
+                Style::default().fg(t.dim),
             )
         };
 
-        let line = Paragraph::new(format!("{}{}", prefix, lang.label()))
+        let line = Paragraph::new(format!("{}{}", prefix, app.library.label(*option)))
             .style(style)
             .alignment(Alignment::Center);
         f.render_widget(line, item_area);
     }
 
-    // Footer hint
-    let footer_y = chunks.y + chunks.height.saturating_sub(2);
-    let footer_area = Rect { x: chunks.x, y: footer_y, width: chunks.width, height: 1 };
-    let footer = Paragraph::new("↑↓ navigate   enter select   q quit")
-        .style(Style::default().fg(DIM))
+    // Current mode and whitespace policy (or word settings), just above the footer
+    let policy_y = chunks.y + chunks.height.saturating_sub(2);
+    let policy_area = Rect { x: chunks.x + 1, y: policy_y, width: chunks.width.saturating_sub(2), height: 1 };
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    let mut spans = vec![
+        Span::styled("mode  ", Style::default().fg(t.dim)),
+        Span::styled(app.mode.label(), Style::default().fg(t.fg)),
+    ];
+    if let Mode::Words(_) = app.mode {
+        spans.extend([
+            Span::styled("   list  ", Style::default().fg(t.dim)),
+            Span::styled(app.word_list.label(), Style::default().fg(t.fg)),
+            Span::styled("   punctuation  ", Style::default().fg(t.dim)),
+            Span::styled(on_off(app.word_options.punctuation), Style::default().fg(t.fg)),
+            Span::styled("   numbers  ", Style::default().fg(t.dim)),
+            Span::styled(on_off(app.word_options.numbers), Style::default().fg(t.fg)),
+        ]);
+    } else {
+        spans.extend([
+            Span::styled("   whitespace  ", Style::default().fg(t.dim)),
+            Span::styled(app.whitespace.label(), Style::default().fg(t.fg)),
+        ]);
+    }
+    spans.extend([
+        Span::styled("   errors  ", Style::default().fg(t.dim)),
+        Span::styled(app.error_policy.label(), Style::default().fg(t.fg)),
+    ]);
+    f.render_widget(Paragraph::new(Line::from(spans)).alignment(Alignment::Center), policy_area);
+
+    // Footer hints, below the panel where the full width is free
+    let footer_y = (chunks.y + chunks.height + 1).min(area.bottom().saturating_sub(2));
+    let footer_area = Rect { x: area.x, y: footer_y, width: area.width, height: 2 };
+    let keys = app.config.keys;
+    let options = match app.mode {
+        Mode::Words(_) => format!(
+            "{} mode   {} list   {} punctuation   {} numbers   {} errors",
+            keys.mode, keys.word_list, keys.punctuation, keys.numbers, keys.errors,
+        ),
+        _ => format!("{} mode   {} whitespace   {} errors", keys.mode, keys.whitespace, keys.errors),
+    };
+    let screens = format!(
+        "↑↓ navigate   enter select   {} history   {} stats   {} theme   {} settings   {} quit",
+        keys.history, keys.stats, keys.theme, keys.settings, keys.quit,
+    );
+    let footer = Paragraph::new(vec![Line::from(screens), Line::from(options)])
+        .style(Style::default().fg(t.dim))
         .alignment(Alignment::Center);
     f.render_widget(footer, footer_area);
 }
@@ -98,6 +156,7 @@ fn draw_menu(f: &mut Frame, app: &App, area: Rect) {
 // ── Typing ────────────────────────────────────────────────────────────────────
 
 fn draw_typing(f: &mut Frame, app: &App, area: Rect) {
+    let t = app.theme();
     let outer = centered_rect(85, 80, area);
 
     // Stats bar at top
@@ -107,24 +166,43 @@ fn draw_typing(f: &mut Frame, app: &App, area: Rect) {
             Constraint::Length(3),  // stats
             Constraint::Min(6),     // snippet
             Constraint::Length(3),  // progress bar
+            Constraint::Length(app.race.as_ref().map_or(0, racers_height)),
             Constraint::Length(1),  // hint
         ])
         .split(outer);
 
-    draw_stats_bar(f, app, layout[0]);
-    draw_snippet(f, app, layout[1]);
-    draw_progress(f, app, layout[2]);
+    let (heading, source) = snippet_heading(app);
+    draw_stats_bar(f, app, &app.session, app.language, app.lead(), layout[0]);
+    draw_snippet(f, app, Pane {
+        session: &app.session,
+        language: app.language,
+        heading,
+        source,
+        ghost: app.ghost.as_ref().map(|g| g.cursor()),
+        pace: app.pace_cursor(),
+    }, layout[1]);
+    draw_progress(f, app, app.session.progress(), layout[2]);
+    if let Some(race) = &app.race {
+        draw_racers(f, app, race, layout[3]);
+    }
 
-    let hint = Paragraph::new("esc → menu   backspace → delete")
-        .style(Style::default().fg(DIM))
+    let hint = if app.session.settings().allow_backspace {
+        "esc → menu   backspace → delete   ctrl+w → delete word   enter/tab → newline/indent"
+    } else {
+        "esc → menu   enter/tab → newline/indent   no backspace"
+    };
+    let hint = Paragraph::new(hint)
+        .style(Style::default().fg(t.dim))
         .alignment(Alignment::Center);
-    f.render_widget(hint, layout[3]);
+    f.render_widget(hint, layout[4]);
 }
 
-fn draw_stats_bar(f: &mut Frame, app: &App, area: Rect) {
+/// `lead` is how many characters the session is ahead of its ghost or pace caret
+fn draw_stats_bar(f: &mut Frame, app: &App, session: &Session, language: Language, lead: Option<isize>, area: Rect) {
+    let t = app.theme();
     let block = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(BORDER));
+        .border_style(Style::default().fg(t.border));
     f.render_widget(block, area);
 
     let inner = inner_rect(area, 1);
@@ -137,17 +215,26 @@ fn draw_stats_bar(f: &mut Frame, app: &App, area: Rect) {
         ])
         .split(inner);
 
-    let lang_text = Paragraph::new(format!("  {}", app.language.label()))
-        .style(Style::default().fg(TITLE_FG).add_modifier(Modifier::BOLD))
+    let lang_text = Paragraph::new(format!("  {}", language.label()))
+        .style(Style::default().fg(t.title).add_modifier(Modifier::BOLD))
         .alignment(Alignment::Left);
 
-    let wpm_text = Paragraph::new(format!("{:.0} wpm", app.wpm))
-        .style(Style::default().fg(FG).add_modifier(Modifier::BOLD))
-        .alignment(Alignment::Center);
+    let mut wpm_spans = vec![Span::styled(
+        format!("{:.0} wpm", session.wpm()),
+        Style::default().fg(t.fg).add_modifier(Modifier::BOLD),
+    )];
+    match lead {
+        Some(lead) if lead > 0 => wpm_spans.push(Span::styled(format!("  ▲ {} ahead", lead), Style::default().fg(t.correct))),
+        Some(lead) if lead < 0 => wpm_spans.push(Span::styled(format!("  ▼ {} behind", -lead), Style::default().fg(t.wrong))),
+        Some(_) => wpm_spans.push(Span::styled("  = level", Style::default().fg(t.dim))),
+        None => {}
+    }
+    let wpm_text = Paragraph::new(Line::from(wpm_spans)).alignment(Alignment::Center);
 
-    let elapsed = app.elapsed().as_secs();
-    let acc_text = Paragraph::new(format!("{:.1}% acc   {:02}:{:02}  ", app.accuracy, elapsed / 60, elapsed % 60))
-        .style(Style::default().fg(FG))
+    // Timed sessions count down instead of up
+    let clock = session.remaining().map_or(session.elapsed(), |left| left + Duration::from_millis(999)).as_secs();
+    let acc_text = Paragraph::new(format!("{:.1}% acc   {:02}:{:02}  ", session.accuracy(), clock / 60, clock % 60))
+        .style(Style::default().fg(t.fg))
         .alignment(Alignment::Right);
 
     f.render_widget(lang_text, chunks[0]);
@@ -155,61 +242,189 @@ fn draw_stats_bar(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(acc_text, chunks[2]);
 }
 
-fn draw_snippet(f: &mut Frame, app: &App, area: Rect) {
-    let block = Block::default()
+/// The snippet pane's title, and its attribution when it has one
+fn snippet_heading(app: &App) -> (String, Option<&str>) {
+    let heading = match app.mode {
+        _ if app.option == MenuOption::Drill => format!(" drill · {} ", app.snippet.title),
+        Mode::Snippet => {
+            let mut heading = format!(" {} · {}", app.snippet.title, app.snippet.difficulty.label());
+            for tag in &app.snippet.tags {
+                heading.push_str(&format!(" #{}", tag));
+            }
+            heading + " "
+        }
+        Mode::Words(_) => format!(" {} · {} ", app.mode.label(), app.snippet.title),
+        mode => format!(" {} ", mode.label()),
+    };
+    let source = app.snippet.source.as_deref().filter(|_| app.mode == Mode::Snippet);
+    (heading, source)
+}
+
+/// What the snippet pane shows: a live session or a replayed one
+struct Pane<'a> {
+    session: &'a Session,
+    language: Language,
+    heading: String,
+    /// Attribution in the bottom border
+    source: Option<&'a str>,
+    /// Where the ghost cursor is, if the session is racing one
+    ghost: Option<usize>,
+    /// Where the pace caret is, if one is set
+    pace: Option<usize>,
+}
+
+fn draw_snippet(f: &mut Frame, app: &App, pane: Pane, area: Rect) {
+    let t = app.theme();
+    let Pane { session, language, heading, source, ghost, pace } = pane;
+    let mut block = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(BORDER));
-    f.render_widget(block.clone(), area);
+        .border_style(Style::default().fg(t.border))
+        .title(Span::styled(heading, Style::default().fg(t.dim)));
+    if let Some(source) = source {
+        block = block.title_bottom(
+            Line::from(Span::styled(format!(" {} ", source), Style::default().fg(t.dim)))
+                .alignment(Alignment::Right),
+        );
+    }
+    f.render_widget(block, area);
 
     let inner = inner_rect(area, 2);
 
-    // Build styled spans from char states
-    let mut lines: Vec<Line> = Vec::new();
-    let mut current_line: Vec<Span> = Vec::new();
+    // Build styled spans from char states, one logical line at a time,
+    // remembering which span is the cursor
+    let tokens = highlight::tokenize(language, session.chars());
+    let mut lines: Vec<Vec<(Span, bool)>> = Vec::new();
+    let mut current_line: Vec<(Span, bool)> = Vec::new();
 
-    for (i, ch) in app.chars.iter().enumerate() {
-        let style = if i == app.cursor {
-            // cursor position
-            Style::default().fg(BG).bg(YELLOW)
-        } else {
-            match app.char_states[i] {
-                CharState::Untyped => Style::default().fg(DIM),
-                CharState::Correct => Style::default().fg(GREEN),
-                CharState::Wrong   => Style::default().fg(RED).add_modifier(Modifier::UNDERLINED),
+    for (i, ch) in session.chars().iter().enumerate() {
+        let style = match session.states()[i] {
+            CharState::Untyped => Style::default().fg(token_color(t, tokens[i])),
+            CharState::Correct => Style::default().fg(t.correct),
+            CharState::Wrong   => Style::default().fg(t.wrong).add_modifier(Modifier::UNDERLINED),
+        };
+        let style = if i == session.cursor() {
+            // A miss held at the cursor by stop-on-error shows in the wrong colour
+            let mark = if session.states()[i] == CharState::Wrong { t.wrong } else { t.accent };
+            match app.config.cursor {
+                CursorStyle::Block => Style::default().fg(t.on_accent()).bg(mark),
+                CursorStyle::Underline => Style::default().fg(mark).add_modifier(Modifier::UNDERLINED | Modifier::BOLD),
+                CursorStyle::Bar => style,
             }
+        } else if pace == Some(i) {
+            style.add_modifier(Modifier::REVERSED)
+        } else if ghost == Some(i) {
+            style.bg(t.border)
+        } else {
+            style
         };
 
         if *ch == '\n' {
             // Add a newline marker span then push the line
-            if i == app.cursor {
-                current_line.push(Span::styled("↵", Style::default().fg(BG).bg(YELLOW)));
+            let marked = i == session.cursor() || ghost == Some(i) || pace == Some(i);
+            if marked || session.states()[i] == CharState::Wrong {
+                current_line.push((Span::styled("↵", style), i == session.cursor()));
             }
-            lines.push(Line::from(current_line.clone()));
-            current_line.clear();
+            lines.push(std::mem::take(&mut current_line));
+        } else if *ch == '\t' {
+            current_line.push((Span::styled(" ".repeat(TAB_WIDTH), style), i == session.cursor()));
         } else {
-            current_line.push(Span::styled(ch.to_string(), style));
+            current_line.push((Span::styled(ch.to_string(), style), i == session.cursor()));
         }
     }
 
     // Cursor at end of last line
-    if app.cursor == app.chars.len() {
-        current_line.push(Span::styled(" ", Style::default().bg(YELLOW)));
+    if session.cursor() == session.chars().len() {
+        let style = match app.config.cursor {
+            CursorStyle::Block => Style::default().bg(t.accent),
+            CursorStyle::Underline => Style::default().fg(t.accent).add_modifier(Modifier::UNDERLINED),
+            CursorStyle::Bar => Style::default(),
+        };
+        current_line.push((Span::styled(" ", style), true));
     }
 
     if !current_line.is_empty() {
-        lines.push(Line::from(current_line));
+        lines.push(current_line);
+    }
+
+    // Soft-wrap ourselves so we know which screen row the cursor is on
+    let mut rows: Vec<Line> = Vec::new();
+    let (mut cursor_row, mut cursor_col) = (0, 0);
+    for line in lines {
+        for row in wrap_spans(line, inner.width as usize) {
+            if let Some(pos) = row.iter().position(|(_, cursor)| *cursor) {
+                cursor_row = rows.len();
+                cursor_col = row[..pos].iter().map(|(s, _)| s.width()).sum::<usize>();
+            }
+            rows.push(Line::from(row.into_iter().map(|(span, _)| span).collect::<Vec<_>>()));
+        }
     }
 
-    let para = Paragraph::new(lines)
-        .wrap(Wrap { trim: false });
+    let top = scroll_offset(cursor_row, rows.len(), inner.height as usize, app.config.scroll_context);
+    let para = Paragraph::new(rows).scroll((top as u16, 0));
     f.render_widget(para, inner);
+
+    // The bar style is the terminal's own cursor, so it sits between characters
+    if app.config.cursor == CursorStyle::Bar && (top..top + inner.height as usize).contains(&cursor_row) {
+        f.set_cursor_position((inner.x + cursor_col as u16, inner.y + (cursor_row - top) as u16));
+    }
+}
+
+/// Cells a tab takes up in the snippet pane
+const TAB_WIDTH: usize = 4;
+
+/// Breaks one logical line into rows of at most `width` cells, after the
+/// last space or tab where possible (like `Wrap`), mid-word where a word
+/// doesn't fit. Spans are measured in cells, so tabs and wide characters
+/// take up as much of the row as they're drawn with.
+fn wrap_spans(line: Vec<(Span, bool)>, width: usize) -> Vec<Vec<(Span, bool)>> {
+    if line.is_empty() || width == 0 {
+        return vec![line];
+    }
+    let mut rows = Vec::new();
+    let mut row: Vec<(Span, bool)> = Vec::new();
+    let mut row_width = 0;
+    for item in line {
+        let w = item.0.width();
+        if row_width + w > width && !row.is_empty() {
+            let split = row.iter()
+                .rposition(|(s, _)| s.content.chars().all(|c| c == ' '))
+                .map_or(row.len(), |i| i + 1);
+            let rest = row.split_off(split);
+            rows.push(std::mem::replace(&mut row, rest));
+            row_width = row.iter().map(|(s, _)| s.width()).sum();
+        }
+        row_width += w;
+        row.push(item);
+    }
+    rows.push(row);
+    rows
 }
 
-fn draw_progress(f: &mut Frame, app: &App, area: Rect) {
-    let pct = (app.progress() * 100.0) as u16;
+/// First row to show so the cursor row has `context` rows visible below it
+/// (and above it, once scrolled), never scrolling past the end of the text
+fn scroll_offset(cursor_row: usize, total_rows: usize, height: usize, context: usize) -> usize {
+    let context = context.min(height.saturating_sub(1) / 2);
+    let wanted = (cursor_row + context + 1).saturating_sub(height);
+    wanted.min(total_rows.saturating_sub(height))
+}
+
+fn token_color(t: &Theme, token: Token) -> Color {
+    match token {
+        Token::Plain       => t.dim,
+        Token::Keyword     => t.keyword,
+        Token::String      => t.string,
+        Token::Number      => t.number,
+        Token::Comment     => t.comment,
+        Token::Punctuation => t.punctuation,
+    }
+}
+
+fn draw_progress(f: &mut Frame, app: &App, progress: f64, area: Rect) {
+    let t = app.theme();
+    let pct = (progress * 100.0) as u16;
     let gauge = Gauge::default()
-        .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(BORDER)))
-        .gauge_style(Style::default().fg(YELLOW).bg(BG))
+        .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(t.border)))
+        .gauge_style(Style::default().fg(t.accent).bg(t.bg))
         .percent(pct)
         .label(format!("{}%", pct));
     f.render_widget(gauge, area);
@@ -218,14 +433,15 @@ fn draw_progress(f: &mut Frame, app: &App, area: Rect) {
 // ── Results ───────────────────────────────────────────────────────────────────
 
 fn draw_results(f: &mut Frame, app: &App, area: Rect) {
-    let panel = centered_rect(48, 55, area);
+    let t = app.theme();
+    let panel = centered_rect(70, 85, area);
 
     let block = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(YELLOW))
+        .border_style(Style::default().fg(t.accent))
         .title(Span::styled(
             "  results  ",
-            Style::default().fg(TITLE_FG).add_modifier(Modifier::BOLD),
+            Style::default().fg(t.title).add_modifier(Modifier::BOLD),
         ))
         .title_alignment(Alignment::Center);
     f.render_widget(block, panel);
@@ -237,39 +453,700 @@ fn draw_results(f: &mut Frame, app: &App, area: Rect) {
         .constraints([
             Constraint::Length(1), // spacer
             Constraint::Length(1), // wpm
-            Constraint::Length(1), // spacer
+            Constraint::Length(1), // wpm vs. bests / average
             Constraint::Length(1), // accuracy
-            Constraint::Length(1), // spacer
+            Constraint::Length(1), // accuracy vs. bests / average
             Constraint::Length(1), // time
-            Constraint::Length(1), // spacer
+            Constraint::Length(1), // consistency
+            Constraint::Length(1), // raw wpm
             Constraint::Length(1), // errors
-            Constraint::Min(2),    // spacer
+            Constraint::Length(1), // spacer
+            Constraint::Min(6),    // wpm chart
+            Constraint::Length(1), // save error
             Constraint::Length(1), // actions
         ])
         .split(inner);
 
-    let elapsed = app.elapsed();
+    let session = &app.session;
+    let elapsed = session.elapsed();
     let secs = elapsed.as_secs_f64();
 
     let stat_rows: &[(usize, &str, String, Color)] = &[
-        (1, "WPM",      format!("{:.0}", app.wpm),      YELLOW),
-        (3, "Accuracy", format!("{:.1}%", app.accuracy), GREEN),
-        (5, "Time",     format!("{:.1}s", secs),          FG),
-        (7, "Errors",   format!("{}", app.errors),        if app.errors == 0 { GREEN } else { RED }),
+        (1, "Net WPM",  format!("{:.0}", session.wpm()),      t.accent),
+        (3, "Accuracy", format!("{:.1}%", session.accuracy()), t.correct),
+        (5, "Time",     format!("{:.1}s", secs),          t.fg),
+        (6, "Consistency", session.consistency().map_or("-".to_string(), |c| format!("{:.0}%", c)), t.fg),
+        (7, "Raw WPM",  format!("{:.0}", session.raw_wpm()),   t.fg),
+        (8, "Errors",   format!("{} uncorrected · {} corrected", session.errors(), session.corrected_errors()),
+            if session.errors() == 0 { t.correct } else { t.wrong }),
     ];
 
     for (idx, label, value, color) in stat_rows {
         let row = Paragraph::new(Line::from(vec![
-            Span::styled(format!("{:<12}", label), Style::default().fg(DIM)),
+            Span::styled(format!("{:<12}", label), Style::default().fg(t.dim)),
             Span::styled(value.clone(), Style::default().fg(*color).add_modifier(Modifier::BOLD)),
         ])).alignment(Alignment::Center);
         f.render_widget(row, layout[*idx]);
     }
 
-    let actions = Paragraph::new("enter/r retry   n new snippet   esc menu")
-        .style(Style::default().fg(DIM))
+    let cmp = &app.comparison;
+    // What the bests were compared across, as `SessionRecord::kind` splits them
+    let kind = match app.mode {
+        _ if app.option == MenuOption::Drill => "drill".to_string(),
+        Mode::Timed(_) | Mode::Words(_) => app.mode.label(),
+        Mode::Snippet => app.language.label().to_string(),
+    };
+    let wpm_note = comparison_line(
+        t, session.wpm(), cmp.best_wpm, cmp.snippet_best_wpm, cmp.avg_wpm, &kind,
+        |v| format!("{:+.0}", v),
+    );
+    let acc_note = comparison_line(
+        t, session.accuracy(), cmp.best_accuracy, cmp.snippet_best_accuracy, cmp.avg_accuracy, &kind,
+        |v| format!("{:+.1}%", v),
+    );
+    let wpm_note = if session.failed() {
+        Line::from(Span::styled(
+            "✗ sudden death: a word was left with a mistake (not saved)",
+            Style::default().fg(t.wrong).add_modifier(Modifier::BOLD),
+        ))
+    } else {
+        wpm_note
+    };
+    f.render_widget(Paragraph::new(wpm_note).alignment(Alignment::Center), layout[2]);
+    f.render_widget(Paragraph::new(acc_note).alignment(Alignment::Center), layout[4]);
+
+    match &app.race {
+        Some(race) => draw_racers(f, app, race, layout[10]),
+        None => draw_wpm_chart(f, app, layout[10]),
+    }
+
+    if let Some(err) = &app.save_error {
+        let warning = Paragraph::new(format!("not saved to history: {}", err))
+            .style(Style::default().fg(t.wrong))
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+        f.render_widget(warning, layout[11]);
+    }
+
+    let keys = app.config.keys;
+    let actions = if app.race.is_some() {
+        format!("enter lobby   {} replay   esc leave race", keys.replay)
+    } else {
+        format!("enter/{} retry   {} new   {} replay   esc menu", keys.retry, keys.new_snippet, keys.replay)
+    };
+    let actions = Paragraph::new(actions)
+        .style(Style::default().fg(t.dim))
+        .alignment(Alignment::Center);
+    f.render_widget(actions, layout[12]);
+}
+
+/// Net WPM over the session, with per-second raw WPM behind it and a red
+/// marker on every second that had a mistake
+fn draw_wpm_chart(f: &mut Frame, app: &App, area: Rect) {
+    let t = app.theme();
+    let samples = app.session.samples();
+    if samples.is_empty() {
+        return;
+    }
+    let net: Vec<(f64, f64)> = samples.iter().map(|s| (s.time, s.wpm)).collect();
+    let raw: Vec<(f64, f64)> = samples.iter().map(|s| (s.time, s.raw_wpm)).collect();
+    let errors: Vec<(f64, f64)> = samples.iter()
+        .filter(|s| s.errors > 0)
+        .map(|s| (s.time, s.raw_wpm))
+        .collect();
+
+    let max_time = samples.last().map_or(1.0, |s| s.time).max(1.0);
+    let max_wpm = samples.iter()
+        .map(|s| s.wpm.max(s.raw_wpm))
+        .fold(0.0, f64::max);
+    // Round the y axis up to a multiple of 20
+    let top = ((max_wpm / 20.0).ceil() * 20.0).max(20.0);
+
+    let datasets = vec![
+        Dataset::default()
+            .name("raw")
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(t.dim))
+            .data(&raw),
+        Dataset::default()
+            .name("wpm")
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(t.accent))
+            .data(&net),
+        Dataset::default()
+            .name("errors")
+            .marker(Marker::Dot)
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(t.wrong))
+            .data(&errors),
+    ];
+    let axis_labels = |max: f64, unit: &str| vec![
+        Span::styled("0", Style::default().fg(t.dim)),
+        Span::styled(format!("{:.0}{}", max, unit), Style::default().fg(t.dim)),
+    ];
+    let chart = Chart::new(datasets)
+        .style(Style::default().fg(t.fg))
+        .x_axis(Axis::default()
+            .style(Style::default().fg(t.border))
+            .bounds([0.0, max_time])
+            .labels(axis_labels(max_time, "s")))
+        .y_axis(Axis::default()
+            .style(Style::default().fg(t.border))
+            .bounds([0.0, top])
+            .labels(axis_labels(top, "")));
+    f.render_widget(chart, area);
+}
+
+/// "★ new best" flags plus the delta against the rolling average
+fn comparison_line(
+    t: &Theme,
+    value: f64,
+    best: Option<f64>,
+    snippet_best: Option<f64>,
+    avg: Option<f64>,
+    kind: &str,
+    fmt_delta: impl Fn(f64) -> String,
+) -> Line<'static> {
+    let mut spans = Vec::new();
+    if is_record(value, best) {
+        spans.push(Span::styled(
+            format!("★ new {} best  ", kind),
+            Style::default().fg(t.accent).add_modifier(Modifier::BOLD),
+        ));
+    } else if is_record(value, snippet_best) {
+        spans.push(Span::styled(
+            "★ new snippet best  ",
+            Style::default().fg(t.accent).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(avg) = avg {
+        let delta = value - avg;
+        let color = if delta >= 0.0 { t.correct } else { t.wrong };
+        spans.push(Span::styled(fmt_delta(delta), Style::default().fg(color)));
+        spans.push(Span::styled(format!(" vs {}-session avg", ROLLING_WINDOW), Style::default().fg(t.dim)));
+    }
+    Line::from(spans)
+}
+
+// ── History ───────────────────────────────────────────────────────────────────
+
+fn draw_history(f: &mut Frame, app: &App, area: Rect) {
+    let t = app.theme();
+    let panel = centered_rect(85, 80, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(t.border))
+        .title(Span::styled(
+            "  history  ",
+            Style::default().fg(t.title).add_modifier(Modifier::BOLD),
+        ))
+        .title_alignment(Alignment::Center);
+    f.render_widget(block, panel);
+
+    let inner = inner_rect(panel, 2);
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // sort / filter summary
+            Constraint::Length(1), // spacer
+            Constraint::Length(1), // column headers
+            Constraint::Min(1),    // rows
+            Constraint::Length(1), // hint
+        ])
+        .split(inner);
+
+    let rows = app.history.view(app.history_sort, app.history_filter);
+    let filter = app.history_filter.map_or("all", |lang| lang.label());
+
+    let summary = Paragraph::new(Line::from(vec![
+        Span::styled("sort ", Style::default().fg(t.dim)),
+        Span::styled(app.history_sort.label(), Style::default().fg(t.fg)),
+        Span::styled("   language ", Style::default().fg(t.dim)),
+        Span::styled(filter, Style::default().fg(t.fg)),
+        Span::styled(format!("   {} sessions", rows.len()), Style::default().fg(t.dim)),
+    ])).alignment(Alignment::Center);
+    f.render_widget(summary, layout[0]);
+
+    let header = Paragraph::new(format!(
+        "{:<16}  {:<8}  {:<24}  {:>5}  {:>7}  {:>6}  {:>7}  {:>5}",
+        "date", "language", "snippet", "wpm", "acc", "errors", "time", "cons",
+    )).style(Style::default().fg(t.dim).add_modifier(Modifier::BOLD));
+    f.render_widget(header, layout[2]);
+
+    if rows.is_empty() {
+        let empty = Paragraph::new("no sessions yet")
+            .style(Style::default().fg(t.dim))
+            .alignment(Alignment::Center);
+        f.render_widget(empty, layout[3]);
+    }
+
+    // The top row is the one Enter replays
+    let lines: Vec<Line> = rows.iter()
+        .skip(app.history_scroll)
+        .take(layout[3].height as usize)
+        .enumerate()
+        .map(|(i, r)| {
+            let title = r.snippet_id.rsplit('/').next().unwrap_or(&r.snippet_id);
+            let title: String = title.chars().take(24).collect();
+            let line = Line::from(vec![
+                Span::styled(
+                    format!("{:<16}  {:<8}  {:<24}  ", format_timestamp(r.timestamp), r.language.label(), title),
+                    Style::default().fg(t.fg),
+                ),
+                Span::styled(format!("{:>5.0}", r.wpm), Style::default().fg(t.accent)),
+                Span::styled(format!("  {:>6.1}%", r.accuracy), Style::default().fg(t.correct)),
+                Span::styled(
+                    format!("  {:>6}", r.errors),
+                    Style::default().fg(if r.errors == 0 { t.correct } else { t.wrong }),
+                ),
+                Span::styled(format!("  {:>6.1}s", r.elapsed), Style::default().fg(t.fg)),
+                Span::styled(
+                    format!("  {:>5}", r.consistency.map_or("-".to_string(), |c| format!("{:.0}%", c))),
+                    Style::default().fg(t.fg),
+                ),
+            ]);
+            if i == 0 { line.style(Style::default().bg(t.border)) } else { line }
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), layout[3]);
+
+    let recorded = rows.get(app.history_scroll).is_some_and(|r| app.history.recording_of(r).is_some());
+    let hint = match &app.history_error {
+        Some(err) => Paragraph::new(err.as_str()).style(Style::default().fg(t.wrong)),
+        None if recorded => Paragraph::new("↑↓ scroll   enter replay   s sort   l language   esc menu")
+            .style(Style::default().fg(t.dim)),
+        None => Paragraph::new("↑↓ scroll   s sort   l language   esc menu")
+            .style(Style::default().fg(t.dim)),
+    };
+    f.render_widget(hint.alignment(Alignment::Center), layout[4]);
+}
+
+// ── Replay ────────────────────────────────────────────────────────────────────
+
+/// A recorded session played back through the same panes as live typing
+fn draw_replay(f: &mut Frame, app: &App, area: Rect) {
+    let t = app.theme();
+    let Some(playback) = &app.playback else { return };
+    let recording = &playback.recording;
+    let session = playback.replay.session();
+    let outer = centered_rect(85, 80, area);
+
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),  // stats
+            Constraint::Min(6),     // snippet
+            Constraint::Length(3),  // progress bar
+            Constraint::Length(1),  // hint
+        ])
+        .split(outer);
+
+    let state = if playback.replay.is_done() {
+        " · finished"
+    } else if playback.paused {
+        " · paused"
+    } else {
+        ""
+    };
+    let heading = format!(" replay · {} · {}x{} ", recording.title, SPEEDS[playback.speed], state);
+    let date = format_timestamp(recording.timestamp);
+
+    draw_stats_bar(f, app, session, recording.language, None, layout[0]);
+    draw_snippet(f, app, Pane {
+        session,
+        language: recording.language,
+        heading,
+        source: Some(&date),
+        ghost: None,
+        pace: None,
+    }, layout[1]);
+    draw_progress(f, app, playback.progress(), layout[2]);
+
+    let hint = Paragraph::new("space pause   ←→ speed   r restart   esc back")
+        .style(Style::default().fg(t.dim))
+        .alignment(Alignment::Center);
+    f.render_widget(hint, layout[3]);
+}
+
+// ── Racing ────────────────────────────────────────────────────────────────────
+
+/// Most racers listed below the progress bar before the list is cut off
+const MAX_RACER_ROWS: usize = 6;
+const RACER_NAME_WIDTH: usize = 12;
+
+/// Rows `draw_racers` needs on the Typing screen
+fn racers_height(race: &Race) -> u16 {
+    race.racers.len().min(MAX_RACER_ROWS) as u16 + 2
+}
+
+/// Waiting for the host to start the next race
+fn draw_lobby(f: &mut Frame, app: &App, area: Rect) {
+    let t = app.theme();
+    let Some(race) = &app.race else { return };
+    let panel = centered_rect(70, 70, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(t.accent))
+        .title(Span::styled(
+            "  race  ",
+            Style::default().fg(t.title).add_modifier(Modifier::BOLD),
+        ))
+        .title_alignment(Alignment::Center);
+    f.render_widget(block, panel);
+
+    let inner = inner_rect(panel, 2);
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // where
+            Constraint::Length(1), // snippet
+            Constraint::Length(1), // spacer
+            Constraint::Min(3),    // racers
+            Constraint::Length(1), // connection error
+            Constraint::Length(1), // hint
+        ])
+        .split(inner);
+
+    let place = if race.is_host() {
+        format!("hosting on port {}", race.address)
+    } else {
+        format!("joined {}", race.address)
+    };
+    let place = Paragraph::new(place)
+        .style(Style::default().fg(t.dim))
+        .alignment(Alignment::Center);
+    f.render_widget(place, layout[0]);
+
+    let snippet = if race.is_host() || race.has_snippet() {
+        Span::styled(
+            format!("{} · {}", app.snippet.title, app.snippet.language.label()),
+            Style::default().fg(t.fg).add_modifier(Modifier::BOLD),
+        )
+    } else {
+        Span::styled("waiting for the host…", Style::default().fg(t.dim))
+    };
+    f.render_widget(Paragraph::new(Line::from(snippet)).alignment(Alignment::Center), layout[1]);
+
+    draw_racers(f, app, race, layout[3]);
+
+    if let Some(err) = &race.error {
+        let warning = Paragraph::new(err.as_str())
+            .style(Style::default().fg(t.wrong))
+            .alignment(Alignment::Center);
+        f.render_widget(warning, layout[4]);
+    }
+
+    let hint = if race.is_host() {
+        format!("enter start   {} new snippet   esc leave", app.config.keys.new_snippet)
+    } else {
+        "the host starts the race   esc leave".to_string()
+    };
+    let hint = Paragraph::new(hint)
+        .style(Style::default().fg(t.dim))
         .alignment(Alignment::Center);
-    f.render_widget(actions, layout[9]);
+    f.render_widget(hint, layout[5]);
+}
+
+/// One progress bar per racer, this instance's own in the accent colour
+fn draw_racers(f: &mut Frame, app: &App, race: &Race, area: Rect) {
+    let t = app.theme();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(t.border))
+        .title(Span::styled(format!(" {} racing ", race.racers.len()), Style::default().fg(t.dim)));
+    f.render_widget(block, area);
+
+    let inner = inner_rect(area, 1);
+    let status_width = 14;
+    let bar_width = (inner.width as usize).saturating_sub(RACER_NAME_WIDTH + status_width + 4);
+    let lines: Vec<Line> = race.racers.iter()
+        .map(|racer| {
+            let colour = if racer.id == race.me { t.accent } else { t.fg };
+            let name: String = racer.name.chars().take(RACER_NAME_WIDTH).collect();
+            let filled = ((racer.progress.clamp(0.0, 1.0) * bar_width as f64).round() as usize).min(bar_width);
+            let status = match racer.place {
+                _ if racer.out => "out".to_string(),
+                Some(place) => format!("{} · {:.0} wpm", ordinal(place), racer.wpm),
+                None => format!("{:.0} wpm", racer.wpm),
+            };
+            Line::from(vec![
+                Span::styled(format!(" {:<w$} ", name, w = RACER_NAME_WIDTH), Style::default().fg(colour)),
+                Span::styled("━".repeat(filled), Style::default().fg(colour)),
+                Span::styled("─".repeat(bar_width - filled), Style::default().fg(t.border)),
+                Span::styled(format!(" {:>w$} ", status, w = status_width), Style::default().fg(t.dim)),
+            ])
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
+/// `1st`, `2nd`, `3rd`, `4th`, …
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+// ── Key stats ─────────────────────────────────────────────────────────────────
+
+fn draw_key_stats(f: &mut Frame, app: &App, area: Rect) {
+    let t = app.theme();
+    let panel = centered_rect(85, 80, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(t.border))
+        .title(Span::styled(
+            "  key stats  ",
+            Style::default().fg(t.title).add_modifier(Modifier::BOLD),
+        ))
+        .title_alignment(Alignment::Center);
+    f.render_widget(block, panel);
+
+    let inner = inner_rect(panel, 2);
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // sort summary
+            Constraint::Length(1), // spacer
+            Constraint::Min(1),    // tables
+            Constraint::Length(1), // hint
+        ])
+        .split(inner);
+
+    let summary = Paragraph::new(Line::from(vec![
+        Span::styled("worst by ", Style::default().fg(t.dim)),
+        Span::styled(app.key_sort.label(), Style::default().fg(t.fg)),
+        Span::styled(format!("   keys seen at least {} times", MIN_SAMPLES), Style::default().fg(t.dim)),
+    ])).alignment(Alignment::Center);
+    f.render_widget(summary, layout[0]);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(layout[2]);
+    for (bigrams, area) in [(false, columns[0]), (true, columns[1])] {
+        let rows = app.key_stats.ranked(bigrams, app.key_sort);
+        let mut lines = vec![Line::from(Span::styled(
+            format!("{:<6}  {:>6}  {:>6}  {:>7}", if bigrams { "bigram" } else { "key" }, "count", "err", "latency"),
+            Style::default().fg(t.dim).add_modifier(Modifier::BOLD),
+        ))];
+        if rows.is_empty() {
+            lines.push(Line::from(Span::styled("not enough data yet", Style::default().fg(t.dim))));
+        }
+        lines.extend(rows.iter()
+            .take(area.height.saturating_sub(1) as usize)
+            .map(|(key, stat)| key_stat_line(t, key, stat)));
+        f.render_widget(Paragraph::new(lines), area);
+    }
+
+    let hint = Paragraph::new("s sort   esc menu")
+        .style(Style::default().fg(t.dim))
+        .alignment(Alignment::Center);
+    f.render_widget(hint, layout[3]);
+}
+
+fn key_stat_line<'a>(t: &Theme, key: &str, stat: &KeyStat) -> Line<'a> {
+    // Whitespace would be invisible in the table
+    let key: String = key.chars()
+        .map(|c| match c {
+            ' ' => '␣',
+            '\n' => '↵',
+            '\t' => '⇥',
+            c => c,
+        })
+        .collect();
+    let latency = stat.mean_latency().map_or("-".to_string(), |ms| format!("{:.0}ms", ms));
+    Line::from(vec![
+        Span::styled(format!("{:<6}  {:>6}  ", key, stat.count()), Style::default().fg(t.fg)),
+        Span::styled(
+            format!("{:>5.1}%", stat.error_rate()),
+            Style::default().fg(if stat.misses == 0 { t.correct } else { t.wrong }),
+        ),
+        Span::styled(format!("  {:>7}", latency), Style::default().fg(t.accent)),
+    ])
+}
+
+// ── Themes ────────────────────────────────────────────────────────────────────
+
+fn draw_themes(f: &mut Frame, app: &App, area: Rect) {
+    let t = app.theme();
+    let panel = centered_rect(70, 70, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(t.border))
+        .title(Span::styled(
+            "  theme  ",
+            Style::default().fg(t.title).add_modifier(Modifier::BOLD),
+        ))
+        .title_alignment(Alignment::Center);
+    f.render_widget(block, panel);
+
+    let inner = inner_rect(panel, 2);
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(20), Constraint::Min(1)])
+        .split(rows[0]);
+
+    let names: Vec<Line> = app.themes.iter().enumerate()
+        .map(|(i, theme)| {
+            if i == app.theme {
+                Line::from(Span::styled(
+                    format!("▶ {}", theme.name),
+                    Style::default().fg(t.accent).add_modifier(Modifier::BOLD),
+                ))
+            } else {
+                Line::from(Span::styled(format!("  {}", theme.name), Style::default().fg(t.dim)))
+            }
+        })
+        .collect();
+    f.render_widget(Paragraph::new(names), columns[0]);
+
+    // A mid-session snapshot: typed, mistyped, cursor and untyped code
+    let preview = vec![
+        Line::from(vec![
+            Span::styled("42 wpm", Style::default().fg(t.fg).add_modifier(Modifier::BOLD)),
+            Span::styled("   97.5% acc", Style::default().fg(t.fg)),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("fn main() {", Style::default().fg(t.correct)),
+        ]),
+        Line::from(vec![
+            Span::styled("    let tota", Style::default().fg(t.correct)),
+            Span::styled("k", Style::default().fg(t.wrong).add_modifier(Modifier::UNDERLINED)),
+            Span::styled(" ", Style::default().fg(t.on_accent()).bg(t.accent)),
+            Span::styled("= ", Style::default().fg(t.punctuation)),
+            Span::styled("40", Style::default().fg(t.number)),
+            Span::styled(" + ", Style::default().fg(t.punctuation)),
+            Span::styled("2", Style::default().fg(t.number)),
+            Span::styled(";", Style::default().fg(t.punctuation)),
+        ]),
+        Line::from(vec![
+            Span::styled("    println!", Style::default().fg(t.dim)),
+            Span::styled("(", Style::default().fg(t.punctuation)),
+            Span::styled("\"{}\"", Style::default().fg(t.string)),
+            Span::styled(", ", Style::default().fg(t.punctuation)),
+            Span::styled("total", Style::default().fg(t.dim)),
+            Span::styled(");", Style::default().fg(t.punctuation)),
+            Span::styled(" // the answer", Style::default().fg(t.comment)),
+        ]),
+        Line::from(vec![
+            Span::styled("    ", Style::default().fg(t.dim)),
+            Span::styled("return", Style::default().fg(t.keyword)),
+            Span::styled(";", Style::default().fg(t.punctuation)),
+        ]),
+        Line::from(Span::styled("}", Style::default().fg(t.punctuation))),
+    ];
+    let preview = Paragraph::new(preview).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(t.border))
+            .title(Span::styled(" preview ", Style::default().fg(t.dim))),
+    );
+    f.render_widget(preview, columns[1]);
+
+    let hint = Paragraph::new("↑↓ preview   enter keep   esc cancel")
+        .style(Style::default().fg(t.dim))
+        .alignment(Alignment::Center);
+    f.render_widget(hint, rows[1]);
+}
+
+// ── Settings ──────────────────────────────────────────────────────────────────
+
+fn draw_settings(f: &mut Frame, app: &App, area: Rect) {
+    let t = app.theme();
+    let panel = centered_rect(60, 85, area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(t.border))
+        .title(Span::styled(
+            "  settings  ",
+            Style::default().fg(t.title).add_modifier(Modifier::BOLD),
+        ))
+        .title_alignment(Alignment::Center);
+    f.render_widget(block, panel);
+
+    let inner = inner_rect(panel, 2);
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(1),    // rows
+            Constraint::Length(1), // save error
+            Constraint::Length(1), // hint
+        ])
+        .split(inner);
+
+    let config = &app.config;
+    let mut rows: Vec<(String, String)> = PREFERENCES.iter()
+        .map(|p| (p.label().to_string(), preference_value(config, *p)))
+        .collect();
+    rows.extend(config.keys.all().iter().map(|(name, key)| (format!("key: {}", name), key.to_string())));
+    debug_assert_eq!(rows.len(), SETTINGS_ROWS);
+
+    // Keep the selection in view on short terminals
+    let height = layout[0].height as usize;
+    let skip = (app.settings_selected + 1).saturating_sub(height);
+    let lines: Vec<Line> = rows.into_iter().enumerate()
+        .skip(skip)
+        .take(height)
+        .map(|(i, (name, value))| {
+            let selected = i == app.settings_selected;
+            let value = if selected && app.settings_capture { "press a key…".to_string() } else { value };
+            let (prefix, name_style) = if selected {
+                ("▶ ", Style::default().fg(t.accent).add_modifier(Modifier::BOLD))
+            } else {
+                ("  ", Style::default().fg(t.dim))
+            };
+            Line::from(vec![
+                Span::styled(format!("{}{:<18}", prefix, name), name_style),
+                Span::styled(value, Style::default().fg(t.fg)),
+            ])
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), layout[0]);
+
+    if let Some(err) = &app.settings_error {
+        let warning = Paragraph::new(err.as_str())
+            .style(Style::default().fg(t.wrong))
+            .alignment(Alignment::Center);
+        f.render_widget(warning, layout[1]);
+    }
+
+    let hint = Paragraph::new("↑↓ select   ←→/enter change   esc menu")
+        .style(Style::default().fg(t.dim))
+        .alignment(Alignment::Center);
+    f.render_widget(hint, layout[2]);
+}
+
+fn preference_value(config: &Config, preference: Preference) -> String {
+    match preference {
+        Preference::Language => config.language.label().into(),
+        Preference::Mode => config.mode.label(),
+        Preference::Theme => config.theme.clone(),
+        Preference::Cursor => config.cursor.label().into(),
+        Preference::Whitespace => config.whitespace.label().into(),
+        Preference::StopOnError => config.stop_on_error.label().into(),
+        Preference::Backspace => if config.allow_backspace { "allowed" } else { "off" }.into(),
+        Preference::ScrollContext => format!("{} rows", config.scroll_context),
+        Preference::Ghost => config.ghost.label().into(),
+        Preference::PaceCaret => if config.pace_wpm == 0 { "off".into() } else { format!("{} wpm", config.pace_wpm) },
+    }
 }
 
 // ── Helpers ───────────────────────────────────────────────────────────────────
@@ -304,3 +1181,376 @@ fn inner_rect(r: Rect, margin: u16) -> Rect {
         height: r.height.saturating_sub(margin * 2),
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ratatui::{Terminal, backend::TestBackend, buffer::Buffer};
+    use rhonetyping::clock::ManualClock;
+    use rhonetyping::engine::{Key, Replay, Settings};
+
+    use super::*;
+    use crate::app::tests::{snippet, test_app, type_text};
+    use crate::ghost::Ghost;
+    use crate::history::History;
+    use crate::keystats::KeyStats;
+    use crate::race::Race;
+    use crate::snippets::Language;
+
+    /// Draws `app` on a 100x30 terminal, big enough for every screen's
+    /// full layout
+    fn render(app: &App) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        terminal.draw(|f| draw(f, app)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    /// The buffer's text, one string per row with trailing spaces trimmed
+    fn text(buffer: &Buffer) -> Vec<String> {
+        buffer.content.chunks(buffer.area.width as usize)
+            .map(|row| row.iter().map(|c| c.symbol()).collect::<String>().trim_end().to_string())
+            .collect()
+    }
+
+    /// A Rust session 1.75s in, with "fn mian" typed
+    fn typing() -> App {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        app.start_snippet(snippet(Language::Rust, "fn main() {\n    println!(\"hi\");\n}"));
+        type_text(&mut app, &clock, "fn mian", Duration::from_millis(250));
+        app.tick();
+        app
+    }
+
+    /// A finished English session with one mistake, saved to a scratch
+    /// data directory that's gone again by the time it's drawn
+    fn results() -> App {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        let dir = std::env::temp_dir()
+            .join(format!("rhonetyping-ui-{}-{:?}", std::process::id(), std::thread::current().id()));
+        app.history = History::open(Some(dir.clone()));
+        app.key_stats = KeyStats::open(Some(dir.clone()));
+        app.start_snippet(snippet(Language::English, "the quick brown fox"));
+        type_text(&mut app, &clock, "the quick briwn fox", Duration::from_millis(150));
+        let _ = std::fs::remove_dir_all(dir);
+        app
+    }
+
+    /// The finished session from `results`, replayed for its first second
+    fn replay() -> App {
+        let clock = ManualClock::new();
+        let mut app = results();
+        app.clock = Box::new(clock.clone());
+        // Recordings are stamped with the real time they finished at
+        app.recording.as_mut().unwrap().timestamp = 0;
+        app.replay_last();
+        clock.advance(Duration::from_secs(1));
+        app.tick();
+        app
+    }
+
+    const MENU: &[&str] = &[
+        "",
+        "",
+        "",
+        "",
+        "",
+        "",
+        "               ┌──────────────────────────  rhonetyping  ───────────────────────────┐",
+        "               │                                                                    │",
+        "               │                    select a mode and press enter                   │",
+        "               │                                                                    │",
+        "               │                                                                    │",
+        "               │                                                                    │",
+        "               │                                                                    │",
+        "               │                             ▶  English                             │",
+        "               │                                                                    │",
+        "               │                                  Rust                              │",
+        "               │                                                                    │",
+        "               │                                 Python                             │",
+        "               │                                                                    │",
+        "               │                                 Drill                              │",
+        "               │                                                                    │",
+        "               │                                                                    │",
+        "               │          mode  snippet   whitespace  literal   errors  off         │",
+        "               └────────────────────────────────────────────────────────────────────┘",
+        "",
+        "          ↑↓ navigate   enter select   h history   s stats   t theme   o settings   q quit",
+        "                                  m mode   w whitespace   e errors",
+        "",
+        "",
+        "",
+    ];
+
+    const TYPING: &[&str] = &[
+        "",
+        "",
+        "",
+        "       ┌───────────────────────────────────────────────────────────────────────────────────┐",
+        "       │  Rust                                34 wpm                    71.4% acc   00:01  │",
+        "       └───────────────────────────────────────────────────────────────────────────────────┘",
+        "       ┌ test · medium ────────────────────────────────────────────────────────────────────┐",
+        "       │                                                                                   │",
+        "       │ fn main() {                                                                       │",
+        "       │     println!(\"hi\");                                                               │",
+        "       │ }                                                                                 │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       └───────────────────────────────────────────────────────────────────────────────────┘",
+        "       ┌───────────────────────────────────────────────────────────────────────────────────┐",
+        "       │█████████████████                       21%                                        │",
+        "       └───────────────────────────────────────────────────────────────────────────────────┘",
+        "        esc → menu   backspace → delete   ctrl+w → delete word   enter/tab → newline/indent",
+        "",
+        "",
+        "",
+    ];
+
+    const RESULTS: &[&str] = &[
+        "",
+        "",
+        "               ┌────────────────────────────  results  ─────────────────────────────┐",
+        "               │                                                                    │",
+        "               │                                                                    │",
+        "               │                           Net WPM     80                           │",
+        "               │                                                                    │",
+        "               │                          Accuracy    94.7%                         │",
+        "               │                                                                    │",
+        "               │                          Time        2.7s                          │",
+        "               │                          Consistency 100%                          │",
+        "               │                           Raw WPM     84                           │",
+        "               │               Errors      1 uncorrected · 0 corrected              │",
+        "               │                                                                    │",
+        "               │ 100│                                                               │",
+        "               │    │                      ⠠⠤⠤⠤⠤⠤⠤⠤⠤⠤⠤⠤⢄⣀⣀⣀⣀⣀⣀⣀⣀⣀⣀•⣀⣀⣀⣀⣀⣀⣀⣀⠤⠤⠤⠤⠤⠤⠤⠤ │",
+        "               │    │                                                               │",
+        "               │    │                                                               │",
+        "               │    │                                                               │",
+        "               │    │                                                               │",
+        "               │    │                                                               │",
+        "               │ 0  │                                                               │",
+        "               │    └────────────────────────────────────────────────────────────── │",
+        "               │    0                                                            3s │",
+        "               │                                                                    │",
+        "               │             enter/r retry   n new   v replay   esc menu            │",
+        "               │                                                                    │",
+        "               └────────────────────────────────────────────────────────────────────┘",
+        "",
+        "",
+    ];
+
+    const REPLAY: &[&str] = &[
+        "",
+        "",
+        "",
+        "       ┌───────────────────────────────────────────────────────────────────────────────────┐",
+        "       │  English                             84 wpm                   100.0% acc   00:01  │",
+        "       └───────────────────────────────────────────────────────────────────────────────────┘",
+        "       ┌ replay · test · 1x ───────────────────────────────────────────────────────────────┐",
+        "       │                                                                                   │",
+        "       │ the quick brown fox                                                               │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       │                                                                                   │",
+        "       └───────────────────────────────────────────────────────────────── 1970-01-01 00:00 ┘",
+        "       ┌───────────────────────────────────────────────────────────────────────────────────┐",
+        "       │███████████████████████████████         37%                                        │",
+        "       └───────────────────────────────────────────────────────────────────────────────────┘",
+        "                           space pause   ←→ speed   r restart   esc back",
+        "",
+        "",
+        "",
+    ];
+
+    /// Hosting a race on a Python snippet with nobody joined yet
+    fn lobby() -> App {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        app.host_race(Race::host(0, "ana".to_string()).unwrap(), None);
+        app.race.as_mut().unwrap().address = "7340".to_string();
+        app.snippet = snippet(Language::Python, "print('hi')");
+        app
+    }
+
+    const LOBBY: &[&str] = &[
+        "",
+        "",
+        "",
+        "",
+        "",
+        "               ┌──────────────────────────────  race  ──────────────────────────────┐",
+        "               │                                                                    │",
+        "               │                        hosting on port 7340                        │",
+        "               │                            test · Python                           │",
+        "               │                                                                    │",
+        "               │ ┌ 1 racing ──────────────────────────────────────────────────────┐ │",
+        "               │ │ ana          ──────────────────────────────────          0 wpm │ │",
+        "               │ │                                                                │ │",
+        "               │ │                                                                │ │",
+        "               │ │                                                                │ │",
+        "               │ │                                                                │ │",
+        "               │ │                                                                │ │",
+        "               │ │                                                                │ │",
+        "               │ │                                                                │ │",
+        "               │ │                                                                │ │",
+        "               │ │                                                                │ │",
+        "               │ └────────────────────────────────────────────────────────────────┘ │",
+        "               │                                                                    │",
+        "               │               enter start   n new snippet   esc leave              │",
+        "               │                                                                    │",
+        "               └────────────────────────────────────────────────────────────────────┘",
+        "",
+        "",
+        "",
+        "",
+    ];
+
+    /// Where `needle` first appears on screen
+    fn find(buffer: &Buffer, needle: &str) -> (u16, u16) {
+        let rows = text(buffer);
+        let (y, row) = rows.iter().enumerate()
+            .find(|(_, row)| row.contains(needle))
+            .expect("text is on screen");
+        let x = row[..row.find(needle).unwrap()].chars().count();
+        (x as u16, y as u16)
+    }
+
+    #[test]
+    fn menu_snapshot() {
+        let clock = ManualClock::new();
+        assert_eq!(text(&render(&test_app(&clock))), MENU);
+    }
+
+    #[test]
+    fn typing_snapshot() {
+        assert_eq!(text(&render(&typing())), TYPING);
+    }
+
+    #[test]
+    fn wrapping_measures_tabs_and_wide_characters_in_cells() {
+        let line = ["a", "\t", "b", "界", "c"].iter()
+            .map(|c| (Span::raw(if *c == "\t" { " ".repeat(TAB_WIDTH) } else { c.to_string() }), false))
+            .collect();
+        let rows: Vec<Vec<String>> = wrap_spans(line, 7).iter()
+            .map(|row| row.iter().map(|(s, _)| s.content.to_string()).collect())
+            .collect();
+        assert_eq!(rows, [vec!["a", "    "], vec!["b", "界", "c"]]);
+    }
+
+    #[test]
+    fn typing_marks_mistakes_and_the_cursor() {
+        let app = typing();
+        let t = app.theme();
+        let buffer = render(&app);
+        let (x, y) = find(&buffer, "fn main()");
+        let cell = |dx: u16| buffer.cell((x + dx, y)).unwrap().clone();
+
+        // "fn mian" over "fn main": two wrong, the rest right
+        assert_eq!(cell(0).fg, t.correct);
+        assert_eq!(cell(3).fg, t.correct);
+        assert_eq!(cell(4).fg, t.wrong);
+        assert_eq!(cell(5).fg, t.wrong);
+        assert_eq!(cell(6).fg, t.correct);
+        // Block cursor on the next character, untyped code after it
+        assert_eq!(cell(7).bg, t.accent);
+        assert_eq!(cell(8).fg, t.punctuation);
+    }
+
+    #[test]
+    fn monochrome_cursor_keeps_its_character_visible() {
+        let mut app = typing();
+        app.theme = app.themes.iter().position(|t| t.name == "monochrome").unwrap();
+        let buffer = render(&app);
+        let (x, y) = find(&buffer, "fn main()");
+        let cursor = buffer.cell((x + 7, y)).unwrap();
+        assert_eq!(cursor.symbol(), "(");
+        assert_eq!((cursor.fg, cursor.bg), (Color::Black, Color::White));
+    }
+
+    #[test]
+    fn ghost_shows_where_it_is_and_the_lead() {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        let text = "fn main() {\n    println!(\"hi\");\n}";
+        // An earlier run at a key every 100ms: 18 characters by the 1.75s mark
+        let mut best = Session::new(text, Settings::default());
+        for (i, c) in text.chars().enumerate() {
+            best.key(Key::Char(c), Duration::from_millis(100 * i as u64));
+        }
+        app.start_snippet(snippet(Language::Rust, text));
+        app.ghost = Some(Ghost::new(Replay::new(text, Settings::default(), best.inputs().to_vec())));
+        type_text(&mut app, &clock, "fn mian", Duration::from_millis(250));
+        app.tick();
+        let t = app.theme();
+        let buffer = render(&app);
+
+        find(&buffer, "▼ 11 behind");
+        let (x, y) = find(&buffer, "println");
+        assert_eq!(buffer.cell((x + 2, y)).unwrap().bg, t.border);
+    }
+
+    #[test]
+    fn pace_caret_runs_ahead_in_its_own_style() {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        // 60 wpm is 5 characters a second: 8 by the 1.75s mark
+        app.config.pace_wpm = 60;
+        app.start_snippet(snippet(Language::Rust, "fn main() {\n    println!(\"hi\");\n}"));
+        type_text(&mut app, &clock, "fn mian", Duration::from_millis(250));
+        app.tick();
+        let t = app.theme();
+        let buffer = render(&app);
+
+        let (x, y) = find(&buffer, "fn main()");
+        assert_eq!(buffer.cell((x + 7, y)).unwrap().bg, t.accent);
+        assert!(buffer.cell((x + 8, y)).unwrap().modifier.contains(Modifier::REVERSED));
+        find(&buffer, "▼ 1 behind");
+    }
+
+    #[test]
+    fn lobby_snapshot() {
+        assert_eq!(text(&render(&lobby())), LOBBY);
+    }
+
+    #[test]
+    fn results_snapshot() {
+        assert_eq!(text(&render(&results())), RESULTS);
+    }
+
+    #[test]
+    fn theme_colours_the_screen() {
+        let clock = ManualClock::new();
+        let mut app = test_app(&clock);
+        app.theme = app.themes.iter().position(|t| t.name == "solarized").unwrap();
+        let buffer = render(&app);
+        let (x, y) = find(&buffer, "┌");
+        assert_eq!(buffer.cell((x, y)).unwrap().fg, Theme::solarized().border);
+    }
+
+    #[test]
+    fn replay_snapshot() {
+        assert_eq!(text(&render(&replay())), REPLAY);
+    }
+}
@@ -0,0 +1,166 @@
+use std::fs;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+
+use crate::paths;
+
+/// The 1000 most common English words, most frequent first
+const COMMON_WORDS: &str = include_str!("../words/english_1k.txt");
+
+/// Where a frequency-ordered top-10k list can be installed, one word per line
+const EXTENDED_FILE: &str = "words/english_10k.txt";
+
+/// How much of the frequency ranking generated words are drawn from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WordList {
+    Top200,
+    Top1k,
+    Top10k,
+}
+
+impl WordList {
+    pub fn label(&self) -> &'static str {
+        match self {
+            WordList::Top200 => "top 200",
+            WordList::Top1k => "top 1k",
+            WordList::Top10k => "top 10k",
+        }
+    }
+
+    /// The next list `words` can draw from
+    pub fn next(&self, words: &Words) -> Self {
+        let next = match self {
+            WordList::Top200 => WordList::Top1k,
+            WordList::Top1k => WordList::Top10k,
+            WordList::Top10k => WordList::Top200,
+        };
+        if words.offers(next) { next } else { next.next(words) }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            WordList::Top200 => 200,
+            WordList::Top1k => 1_000,
+            WordList::Top10k => 10_000,
+        }
+    }
+}
+
+/// Extras mixed into a generated stream so it reads more like real prose
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WordOptions {
+    /// Capitalised sentences with commas and closing punctuation
+    pub punctuation: bool,
+    /// Occasional numbers in place of words
+    pub numbers: bool,
+}
+
+/// Frequency-ranked English words, most common first
+pub struct Words {
+    ranked: Vec<String>,
+}
+
+impl Words {
+    /// Loads the built-in top-1k list, replaced by a user-installed
+    /// top-10k list from the config directory when one exists. Without
+    /// it the top-10k setting isn't offered.
+    pub fn load() -> Words {
+        let extended = paths::config_dir()
+            .and_then(|d| fs::read_to_string(d.join(EXTENDED_FILE)).ok())
+            .map(|text| parse(&text))
+            .filter(|words| words.len() > COMMON_WORDS.lines().count());
+        match extended {
+            Some(ranked) => Words { ranked },
+            None => Words::builtin(),
+        }
+    }
+
+    /// The built-in top-1k list alone
+    pub fn builtin() -> Words {
+        Words { ranked: parse(COMMON_WORDS) }
+    }
+
+    /// Whether there are more words than the list below `list` has, so
+    /// choosing it makes a difference
+    pub fn offers(&self, list: WordList) -> bool {
+        match list {
+            WordList::Top10k => self.ranked.len() > WordList::Top1k.size(),
+            WordList::Top200 | WordList::Top1k => true,
+        }
+    }
+
+    /// Every word in the ranking, most common first
+    pub fn ranked(&self) -> &[String] {
+        &self.ranked
+    }
+
+    /// Generates `count` random words from the top of the ranking
+    pub fn generate(&self, list: WordList, count: usize, options: WordOptions) -> String {
+        let mut rng = thread_rng();
+        let pool = &self.ranked[..list.size().min(self.ranked.len())];
+        let mut out = String::new();
+        // Words left in the current sentence; 0 starts a new one
+        let mut sentence_left = 0;
+
+        for i in 0..count {
+            let mut word = if options.numbers && rng.gen_bool(0.1) {
+                rng.gen_range(1..=9999).to_string()
+            } else {
+                pool.choose(&mut rng).expect("word list is not empty").clone()
+            };
+
+            if options.punctuation {
+                if sentence_left == 0 {
+                    sentence_left = rng.gen_range(4..=12);
+                    word = capitalize(&word);
+                }
+                sentence_left -= 1;
+                if sentence_left == 0 || i + 1 == count {
+                    word.push(*['.', '.', '.', '?', '!'].choose(&mut rng).unwrap());
+                    sentence_left = 0;
+                } else if rng.gen_bool(0.1) {
+                    word.push(',');
+                }
+            }
+
+            if i > 0 {
+                out.push(' ');
+            }
+            out.push_str(&word);
+        }
+        out
+    }
+}
+
+/// One lowercase ASCII word per line; anything else is ignored
+fn parse(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|w| !w.is_empty() && w.chars().all(|c| c.is_ascii_alphabetic()))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_10k_is_only_offered_with_the_list_installed() {
+        let builtin = Words::builtin();
+        assert_eq!(WordList::Top1k.next(&builtin), WordList::Top200);
+
+        let extended = Words { ranked: (0..10_000).map(|i| format!("w{}", i)).collect() };
+        assert_eq!(WordList::Top1k.next(&extended), WordList::Top10k);
+    }
+}
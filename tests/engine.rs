@@ -0,0 +1,260 @@
+//! Replays scripted keystrokes with fixed timestamps through the engine
+//! and checks the exact stats that come out.
+
+use std::time::Duration;
+
+use rhonetyping::engine::{CharState, ErrorPolicy, Key, Replay, Session, Settings, WhitespacePolicy};
+
+/// Feeds `script` to a fresh session over `text`. Each step is a key and
+/// the milliseconds since the previous step; the first key lands at 0.
+fn play(text: &str, settings: Settings, script: &[(u64, Key)]) -> Session {
+    let mut session = Session::new(text, settings);
+    let mut now = Duration::ZERO;
+    for (i, (gap, key)) in script.iter().enumerate() {
+        if i > 0 {
+            now += Duration::from_millis(*gap);
+        }
+        session.key(*key, now);
+    }
+    session
+}
+
+/// Types `text` at one key every `gap` milliseconds
+fn typed(text: &str, gap: u64) -> Vec<(u64, Key)> {
+    text.chars().map(|c| (gap, Key::Char(c))).collect()
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+}
+
+#[test]
+fn perfect_run() {
+    // 10 chars, 9 gaps of 500ms: 2 words in 4.5s
+    let session = play("hello word", Settings::default(), &typed("hello word", 500));
+    assert!(session.is_finished());
+    assert_eq!(session.elapsed(), Duration::from_millis(4500));
+    assert_close(session.wpm(), 2.0 / (4.5 / 60.0));
+    assert_close(session.raw_wpm(), session.wpm());
+    assert_close(session.accuracy(), 100.0);
+    assert_eq!(session.errors(), 0);
+    assert!(!session.failed());
+}
+
+#[test]
+fn mistakes_count_against_net_wpm_and_accuracy() {
+    let session = play("hello word", Settings::default(), &typed("jello wore", 500));
+    assert!(session.is_finished());
+    assert_eq!(session.errors(), 2);
+    assert_eq!(session.states()[0], CharState::Wrong);
+    assert_close(session.wpm(), 8.0 / 5.0 / (4.5 / 60.0));
+    assert_close(session.raw_wpm(), 2.0 / (4.5 / 60.0));
+    assert_close(session.accuracy(), 80.0);
+}
+
+#[test]
+fn corrections_dont_undo_a_miss() {
+    let mut script = typed("hx", 250);
+    script.push((250, Key::Backspace));
+    script.extend(typed("i!", 250));
+    let session = play("hi!", Settings::default(), &script);
+    assert!(session.is_finished());
+    assert_eq!(session.errors(), 0);
+    assert_eq!(session.corrected_errors(), 1);
+    assert_eq!(session.keystrokes().len(), 4);
+    assert_close(session.accuracy(), 75.0);
+    assert_close(session.wpm(), 3.0 / 5.0 / (1.0 / 60.0));
+}
+
+#[test]
+fn nothing_counts_before_the_first_key() {
+    let mut session = Session::new("abc", Settings::default());
+    session.tick(Duration::from_secs(5));
+    assert!(!session.is_started());
+    assert_eq!(session.elapsed(), Duration::ZERO);
+
+    // The clock starts at the first key, wherever it falls
+    session.key(Key::Char('a'), Duration::from_secs(10));
+    session.key(Key::Char('b'), Duration::from_millis(10_500));
+    assert_eq!(session.elapsed(), Duration::from_millis(500));
+    assert_close(session.wpm(), 2.0 / 5.0 / (0.5 / 60.0));
+}
+
+#[test]
+fn time_limit_ends_the_session_exactly() {
+    let settings = Settings { time_limit: Some(Duration::from_secs(2)), ..Settings::default() };
+    let mut session = play("abcdef", settings, &typed("abc", 600));
+    assert!(!session.is_finished());
+    assert_eq!(session.remaining(), Some(Duration::from_millis(800)));
+
+    // A late key is past the limit: it ends the session instead of counting
+    session.key(Key::Char('d'), Duration::from_millis(2300));
+    assert!(session.is_finished());
+    assert_eq!(session.cursor(), 3);
+    assert_eq!(session.elapsed(), Duration::from_secs(2));
+    assert_close(session.wpm(), 3.0 / 5.0 / (2.0 / 60.0));
+    assert_close(session.progress(), 1.0);
+}
+
+#[test]
+fn samples_cover_every_second() {
+    // One key every 250ms for 2.5s
+    let session = play("abcdefghijk", Settings::default(), &typed("abcdefghijk", 250));
+    let samples = session.samples();
+    assert_eq!(samples.len(), 3);
+    assert_close(samples[0].time, 1.0);
+    assert_close(samples[1].time, 2.0);
+    assert_close(samples[2].time, 2.5);
+    // 4 keys in each whole second, 3 in the last half (2.0, 2.25, 2.5)
+    assert_close(samples[0].raw_wpm, 4.0 / 5.0 * 60.0);
+    assert_close(samples[1].raw_wpm, 4.0 / 5.0 * 60.0);
+    assert_close(samples[2].raw_wpm, 3.0 / 5.0 / (0.5 / 60.0));
+    assert_eq!(session.consistency(), Some(100.0));
+}
+
+#[test]
+fn consistency_needs_two_seconds() {
+    let session = play("abc", Settings::default(), &typed("abc", 400));
+    assert_eq!(session.consistency(), None);
+}
+
+#[test]
+fn stop_on_letter_holds_the_cursor() {
+    let settings = Settings { error_policy: ErrorPolicy::Letter, ..Settings::default() };
+    let session = play("ab", settings, &typed("axb", 500));
+    assert!(session.is_finished());
+    assert_eq!(session.errors(), 0);
+    assert_eq!(session.corrected_errors(), 1);
+    assert_close(session.accuracy(), 200.0 / 3.0);
+}
+
+#[test]
+fn repeated_misses_on_a_held_letter_are_one_correction() {
+    let settings = Settings { error_policy: ErrorPolicy::Letter, ..Settings::default() };
+    let mut session = play("ab", settings, &typed("axx", 500));
+    assert_eq!(session.corrected_errors(), 0);
+    session.key(Key::Char('b'), Duration::from_millis(1500));
+    assert!(session.is_finished());
+    assert_eq!(session.corrected_errors(), 1);
+}
+
+#[test]
+fn stop_on_word_blocks_the_space() {
+    let settings = Settings { error_policy: ErrorPolicy::Word, ..Settings::default() };
+    let mut session = play("ab cd", settings, &typed("ax ", 100));
+    assert_eq!(session.cursor(), 2);
+    session.key(Key::Backspace, Duration::from_millis(300));
+    session.key(Key::Char('b'), Duration::from_millis(400));
+    session.key(Key::Char(' '), Duration::from_millis(500));
+    assert_eq!(session.cursor(), 3);
+}
+
+#[test]
+fn stop_on_word_cant_block_without_backspace() {
+    let settings = Settings { error_policy: ErrorPolicy::Word, allow_backspace: false, ..Settings::default() };
+    let session = play("ab cd", settings, &typed("ax cd", 100));
+    assert!(session.is_finished());
+    assert_eq!(session.errors(), 1);
+}
+
+#[test]
+fn sudden_death_fails_at_the_end_of_a_word() {
+    let settings = Settings { error_policy: ErrorPolicy::SuddenDeath, ..Settings::default() };
+    let mut session = play("ab cd", settings, &typed("ax", 100));
+    assert!(!session.is_finished());
+    session.key(Key::Char(' '), Duration::from_millis(200));
+    assert!(session.is_finished());
+    assert!(session.failed());
+}
+
+#[test]
+fn skipped_indentation_isnt_typed_or_counted() {
+    let text = "if x:\n    y";
+    let settings = Settings { whitespace: WhitespacePolicy::SkipIndent, ..Settings::default() };
+    let session = play(text, settings, &typed("if x:\ny", 500));
+    assert!(session.is_finished());
+    assert_eq!(session.keystrokes().len(), 7);
+    assert_close(session.wpm(), 7.0 / 5.0 / (3.0 / 60.0));
+
+    // Backspace takes the indentation away together with the newline
+    let mut script = typed("if x:\n", 500);
+    script.push((500, Key::Backspace));
+    let session = play(text, settings, &script);
+    assert_eq!(session.cursor(), 5);
+}
+
+#[test]
+fn tab_types_one_indent_unit() {
+    let session = play("a\n  b", Settings::default(), &[(0, Key::Char('a')), (100, Key::Char('\n')), (100, Key::Tab)]);
+    assert_eq!(session.cursor(), 4);
+    assert_eq!(session.errors(), 0);
+}
+
+#[test]
+fn sudden_death_stops_a_tab_partway() {
+    let settings = Settings { error_policy: ErrorPolicy::SuddenDeath, ..Settings::default() };
+    let mut script = typed("a\n", 100);
+    script.push((100, Key::Tab));
+    script.extend(typed("b\n", 100));
+    // The four-space unit misses the "x" and fails on the space after it
+    script.push((100, Key::Tab));
+    let session = play("a\n    b\nx  yz", settings, &script);
+    assert!(session.failed());
+    assert_eq!(session.cursor(), 10);
+    assert_eq!(session.keystrokes().len(), 10);
+    assert_eq!(session.states()[10], CharState::Untyped);
+}
+
+#[test]
+fn delete_word_goes_back_to_the_word_start() {
+    let mut script = typed("one twx", 100);
+    script.push((100, Key::DeleteWord));
+    let session = play("one two three", Settings::default(), &script);
+    assert_eq!(session.cursor(), 4);
+    assert_eq!(session.errors(), 0);
+    assert_eq!(session.corrected_errors(), 1);
+}
+
+#[test]
+fn backspace_can_be_turned_off() {
+    let settings = Settings { allow_backspace: false, ..Settings::default() };
+    let mut script = typed("ax", 100);
+    script.push((100, Key::Backspace));
+    script.push((100, Key::DeleteWord));
+    let session = play("abc", settings, &script);
+    assert_eq!(session.cursor(), 2);
+    assert_eq!(session.errors(), 1);
+}
+
+#[test]
+fn replay_reproduces_the_session() {
+    let mut script = typed("the quix", 180);
+    script.push((400, Key::Backspace));
+    script.extend(typed("ck brwn", 150));
+    script.push((300, Key::DeleteWord));
+    script.extend(typed("brown", 120));
+    let session = play("the quick brown", Settings::default(), &script);
+    assert!(session.is_finished());
+
+    let mut replay = Replay::new("the quick brown", session.settings(), session.inputs().to_vec());
+    replay.advance_to(replay.duration());
+    let replayed = replay.session();
+    assert!(replay.is_done());
+    assert_eq!(replayed.states(), session.states());
+    assert_eq!(replayed.elapsed(), session.elapsed());
+    assert_eq!(replayed.corrected_errors(), session.corrected_errors());
+    assert_close(replayed.wpm(), session.wpm());
+    assert_close(replayed.accuracy(), session.accuracy());
+}
+
+#[test]
+fn replay_stops_at_the_playback_time() {
+    let session = play("abcd", Settings::default(), &typed("abcd", 1000));
+    let mut replay = Replay::new("abcd", Settings::default(), session.inputs().to_vec());
+    replay.advance_to(Duration::from_millis(1500));
+    assert_eq!(replay.session().cursor(), 2);
+    assert_eq!(replay.session().elapsed(), Duration::from_millis(1500));
+    assert!(!replay.is_done());
+    replay.advance_to(Duration::from_secs(3));
+    assert!(replay.is_done());
+}